@@ -0,0 +1,17 @@
+-- Add migration script here
+ALTER TABLE verification ADD COLUMN created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP;
+ALTER TABLE verification ADD COLUMN updated TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+CREATE TABLE verification_history (
+    id SERIAL PRIMARY KEY,
+    checksum VARCHAR(64) NOT NULL,
+    verifier_id BIGINT NOT NULL,
+    action TEXT NOT NULL, -- 'submit', 'amend' or 'retract'
+    is_good BOOLEAN, -- NULL when the vote was retracted
+    reason TEXT,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Votes submitted before this migration have no history yet.
+INSERT INTO verification_history (checksum, verifier_id, action, is_good, reason)
+    SELECT checksum, verifier_id, 'submit', is_good, reason FROM verification;
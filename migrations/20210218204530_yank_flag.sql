@@ -0,0 +1,22 @@
+-- Add migration script here
+ALTER TABLE mods ADD COLUMN yanked BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE mods ADD COLUMN yank_reason TEXT;
+ALTER TABLE mods ADD COLUMN yanked_at TIMESTAMPTZ;
+
+-- Yanking used to overwrite the verification level, so recover it from the votes, and Core from
+-- the other versions of the mod, as Core is given to whole mods.
+-- Nothing recorded which yanked versions were Auto, so those can't be recovered and come out
+-- unverified, to be verified again.
+UPDATE mods SET
+    yanked = true,
+    yanked_at = CURRENT_TIMESTAMP,
+    verification = CASE
+        WHEN EXISTS (SELECT 1 FROM mods o WHERE o.name = mods.name AND o.verification = 'Core')
+            THEN 'Core'::verification_level
+        WHEN (SELECT COUNT(*) FROM verification v WHERE v.checksum = mods.checksum AND NOT v.is_good) >= 2
+            THEN 'Unsafe'::verification_level
+        WHEN (SELECT COUNT(*) FROM verification v WHERE v.checksum = mods.checksum AND v.is_good) >= 2
+            THEN 'Manual'::verification_level
+        ELSE NULL
+    END
+WHERE verification = 'Yanked';
@@ -0,0 +1,11 @@
+-- Add migration script here
+ALTER TABLE mods ADD COLUMN unsafe_reason TEXT; -- Why an admin forced the mod as Unsafe
+
+CREATE TABLE verification_overrides (
+    id SERIAL PRIMARY KEY,
+    checksum VARCHAR(64) NOT NULL,
+    admin_id BIGINT NOT NULL,
+    verification VERIFICATION_LEVEL, -- NULL when the verification was reset to None
+    reason TEXT,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,41 @@
+-- Add migration script here
+CREATE TYPE ADVISORY_SEVERITY AS ENUM (
+    'Low',
+    'Medium',
+    'High',
+    'Critical'
+);
+
+CREATE TABLE advisories (
+    id SERIAL PRIMARY KEY,
+    mod_name TEXT NOT NULL,
+    versions TEXT NOT NULL, -- SemVer requirement of the affected versions
+    severity ADVISORY_SEVERITY NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    author_id BIGINT NOT NULL,
+    withdrawn BOOLEAN NOT NULL DEFAULT false,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX advisories_mod_name ON advisories (mod_name);
+
+-- The reason of the mods already forced as Unsafe is shown to users as an advisory.
+INSERT INTO advisories (mod_name, versions, severity, title, description, author_id)
+    SELECT
+        mods.name,
+        '=' || mods.version,
+        'Critical',
+        'Marked as Unsafe by an admin',
+        mods.unsafe_reason,
+        COALESCE(
+            (
+                SELECT admin_id FROM verification_overrides
+                WHERE checksum = mods.checksum
+                ORDER BY created DESC
+                LIMIT 1
+            ),
+            0
+        )
+    FROM mods
+    WHERE mods.unsafe_reason IS NOT NULL;
@@ -0,0 +1,19 @@
+-- Add migration script here
+-- Hashes of the files inside each mod archive.
+-- Only archives uploaded after this migration have their files hashed.
+CREATE TABLE mod_files (
+    checksum VARCHAR(64) NOT NULL, -- mod package checksum
+    filename TEXT NOT NULL,
+    hash VARCHAR(64) NOT NULL,
+    PRIMARY KEY (checksum, filename)
+);
+
+CREATE INDEX mod_files_hash ON mod_files (hash);
+
+-- Known malware, matched against both mod package checksums and the files inside them.
+CREATE TABLE blocklist (
+    hash VARCHAR(64) PRIMARY KEY,
+    reason TEXT NOT NULL,
+    admin_id BIGINT NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
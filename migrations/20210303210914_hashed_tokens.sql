@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Only the SHA-256 of each token is stored from now on.
+-- Existing tokens are hashed in place, so they keep working.
+ALTER TABLE tokens RENAME COLUMN token TO token_hash;
+UPDATE tokens SET token_hash = encode(sha256(convert_to(token_hash, 'UTF8')), 'hex');
+ALTER TABLE tokens ADD CONSTRAINT tokens_token_hash_key UNIQUE (token_hash);
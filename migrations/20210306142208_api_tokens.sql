@@ -0,0 +1,18 @@
+-- Add migration script here
+CREATE TABLE api_tokens (
+    id SERIAL PRIMARY KEY,
+    owner_id BIGINT NOT NULL REFERENCES tokens (owner_id) ON DELETE CASCADE,
+    name VARCHAR(64) NOT NULL,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    expires TIMESTAMPTZ,
+    last_used TIMESTAMPTZ,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (owner_id, name)
+);
+
+-- The existing tokens could do everything, so they keep every scope.
+INSERT INTO api_tokens (owner_id, name, token_hash, scopes)
+    SELECT owner_id, 'default', token_hash, ARRAY['publish', 'yank', 'verify', 'read', 'admin'] FROM tokens;
+
+ALTER TABLE tokens DROP COLUMN token_hash;
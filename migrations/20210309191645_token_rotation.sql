@@ -0,0 +1,19 @@
+-- Add migration script here
+-- The previous hash keeps working until `previous_expires` after a rotation with a grace period.
+ALTER TABLE api_tokens ADD COLUMN previous_token_hash VARCHAR(64) UNIQUE;
+ALTER TABLE api_tokens ADD COLUMN previous_expires TIMESTAMPTZ;
+
+CREATE TABLE token_events (
+    id SERIAL PRIMARY KEY,
+    owner_id BIGINT NOT NULL,
+    token_id INT, -- not a foreign key, the events are kept after the token is revoked
+    token_name VARCHAR(64) NOT NULL,
+    event TEXT NOT NULL, -- 'created', 'rotated', 'revoked' or 'used'
+    ip TEXT,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX token_events_owner_id ON token_events (owner_id, created);
+
+INSERT INTO token_events (owner_id, token_id, token_name, event, created)
+    SELECT owner_id, id, name, 'created', created FROM api_tokens;
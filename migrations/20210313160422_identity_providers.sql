@@ -0,0 +1,18 @@
+-- Add migration script here
+-- Ids of accounts created from now on, existing Discord users keep their Discord id.
+-- Starts above the team ids and far below the Discord ids, so they can't collide.
+CREATE SEQUENCE user_ids START 4294967296;
+
+CREATE TABLE linked_accounts (
+    provider TEXT NOT NULL,
+    external_id TEXT NOT NULL,
+    user_id BIGINT NOT NULL REFERENCES tokens (owner_id) ON DELETE CASCADE,
+    username TEXT,
+    email TEXT,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (provider, external_id),
+    UNIQUE (user_id, provider)
+);
+
+INSERT INTO linked_accounts (provider, external_id, user_id, email)
+    SELECT 'discord', owner_id::text, owner_id, email FROM tokens WHERE NOT is_team;
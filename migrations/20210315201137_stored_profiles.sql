@@ -0,0 +1,16 @@
+-- Add migration script here
+-- Profile of the account the user last logged in with, shown on the site and in /me.
+ALTER TABLE tokens ADD COLUMN username TEXT;
+ALTER TABLE tokens ADD COLUMN discriminator TEXT;
+ALTER TABLE tokens ADD COLUMN avatar_url TEXT;
+ALTER TABLE tokens ADD COLUMN profile_provider TEXT;
+ALTER TABLE tokens ADD COLUMN profile_updated TIMESTAMPTZ;
+
+-- OAuth2 tokens of each linked account, so the profile can be refreshed without the user.
+ALTER TABLE linked_accounts ADD COLUMN access_token TEXT;
+ALTER TABLE linked_accounts ADD COLUMN refresh_token TEXT;
+ALTER TABLE linked_accounts ADD COLUMN token_expires TIMESTAMPTZ;
+
+-- The usernames of linked accounts are the best there is until the users log in again.
+UPDATE tokens SET username = linked_accounts.username, profile_provider = linked_accounts.provider
+    FROM linked_accounts WHERE linked_accounts.user_id = tokens.owner_id AND linked_accounts.username IS NOT NULL;
@@ -0,0 +1,16 @@
+-- Add migration script here
+-- Cookies now hold a random session id instead of the user id, only its hash is stored.
+-- Every user has to log in again after this migration.
+CREATE TABLE sessions (
+    id SERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES tokens (owner_id) ON DELETE CASCADE,
+    session_hash TEXT NOT NULL UNIQUE,
+    provider TEXT, -- the identity provider used to log in
+    ip TEXT,
+    user_agent TEXT,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX sessions_user_id ON sessions (user_id);
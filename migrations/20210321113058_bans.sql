@@ -0,0 +1,9 @@
+-- Add migration script here
+ALTER TABLE tokens ADD COLUMN ban_reason TEXT;
+ALTER TABLE tokens ADD COLUMN banned_by BIGINT;
+ALTER TABLE tokens ADD COLUMN banned_at TIMESTAMPTZ;
+-- NULL for permanent bans, the account is no longer banned once it passes.
+ALTER TABLE tokens ADD COLUMN ban_expires TIMESTAMPTZ;
+
+-- Bans from before this migration were done by hand.
+UPDATE tokens SET ban_reason = 'Banned before ban reasons were recorded.' WHERE is_banned;
@@ -0,0 +1,58 @@
+-- Add migration script here
+CREATE TABLE audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    -- The user or team that did the action, no foreign keys so entries outlive what they refer to.
+    actor_id BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    mod_name TEXT,
+    checksum VARCHAR(64),
+    team_id INT,
+    -- The account affected, for bans and role changes.
+    target_id BIGINT,
+    details TEXT,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX audit_log_actor_id ON audit_log (actor_id);
+CREATE INDEX audit_log_mod_name ON audit_log (mod_name);
+CREATE INDEX audit_log_team_id ON audit_log (team_id);
+CREATE INDEX audit_log_created ON audit_log (created);
+
+CREATE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'The audit log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE PROCEDURE audit_log_append_only();
+
+-- Verifications were already recorded, keep them in the log.
+INSERT INTO audit_log (actor_id, action, mod_name, checksum, details, created)
+SELECT
+    h.verifier_id,
+    CASE h.action WHEN 'retract' THEN 'retract_verification' ELSE 'verify' END,
+    m.name,
+    h.checksum,
+    CASE
+        WHEN h.action = 'retract' THEN NULL
+        WHEN h.is_good THEN 'Good vote'
+        ELSE 'Bad vote: ' || COALESCE(h.reason, '')
+    END,
+    h.created
+FROM verification_history h
+LEFT JOIN mods m ON m.checksum = h.checksum
+ORDER BY h.id;
+
+INSERT INTO audit_log (actor_id, action, mod_name, checksum, details, created)
+SELECT
+    o.admin_id,
+    'override_verification',
+    m.name,
+    o.checksum,
+    COALESCE(o.verification::text, 'None') || COALESCE(': ' || o.reason, ''),
+    o.created
+FROM verification_overrides o
+LEFT JOIN mods m ON m.checksum = o.checksum
+ORDER BY o.id;
@@ -0,0 +1,33 @@
+-- Add migration script here
+CREATE TABLE team_invites (
+    id SERIAL PRIMARY KEY,
+    team_id INT NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    code VARCHAR(32) NOT NULL UNIQUE,
+    -- NULL for the invites from before there could be several per team.
+    created_by BIGINT,
+    -- Only this user can join with the invite when set.
+    target_user BIGINT,
+    -- The team roles members that join with the invite get.
+    roles INT NOT NULL DEFAULT 0,
+    max_uses INT,
+    uses INT NOT NULL DEFAULT 0,
+    expires TIMESTAMPTZ,
+    revoked BOOLEAN NOT NULL DEFAULT false,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX team_invites_team_id ON team_invites (team_id);
+
+CREATE TABLE team_invite_uses (
+    invite_id INT NOT NULL REFERENCES team_invites(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX team_invite_uses_invite_id ON team_invite_uses (invite_id);
+
+-- The old invites keep working for another week.
+INSERT INTO team_invites (team_id, code, expires)
+SELECT id, invite, CURRENT_TIMESTAMP + INTERVAL '7 days' FROM teams WHERE invite IS NOT NULL;
+
+ALTER TABLE teams DROP COLUMN invite;
@@ -0,0 +1,15 @@
+-- Add migration script here
+-- Permissions of a member over a mod of the team, instead of the ones from their team roles.
+CREATE TABLE team_mod_permissions (
+    team_id INT NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    mod_name TEXT NOT NULL,
+    member BIGINT NOT NULL,
+    permissions INT NOT NULL,
+    PRIMARY KEY (team_id, mod_name, member)
+);
+
+-- The user or team that uploaded or yanked a version, members of a team are recorded themselves.
+ALTER TABLE mods ADD COLUMN uploaded_by BIGINT;
+ALTER TABLE mods ADD COLUMN yanked_by BIGINT;
+
+UPDATE mods SET uploaded_by = owners.owner_id FROM owners WHERE owners.mod_name = mods.name;
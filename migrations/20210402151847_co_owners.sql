@@ -0,0 +1,24 @@
+-- Add migration script here
+-- A mod can have several owners, users or teams.
+ALTER TABLE owners DROP CONSTRAINT owners_pkey;
+ALTER TABLE owners ADD PRIMARY KEY (mod_name, owner_id);
+
+CREATE INDEX owners_owner_id ON owners (owner_id);
+
+-- Pending invites to become an owner of a mod, the invitee has to accept them.
+CREATE TABLE ownership_invites (
+    id SERIAL PRIMARY KEY,
+    mod_name TEXT NOT NULL,
+    -- The owner that sent the invite, a user or a team.
+    from_owner BIGINT NOT NULL,
+    invited_by BIGINT NOT NULL,
+    invitee_id BIGINT NOT NULL,
+    invitee_is_team BOOLEAN NOT NULL,
+    -- `from_owner` stops being an owner when the invite is accepted.
+    transfer BOOLEAN NOT NULL DEFAULT false,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires TIMESTAMPTZ NOT NULL,
+    UNIQUE (mod_name, invitee_id)
+);
+
+CREATE INDEX ownership_invites_invitee_id ON ownership_invites (invitee_id);
@@ -0,0 +1,9 @@
+-- Add migration script here
+ALTER TABLE teams ADD COLUMN description TEXT;
+ALTER TABLE teams ADD COLUMN avatar_url TEXT;
+
+-- Team names are unique regardless of case, teams that clash get their id added to the name.
+UPDATE teams t SET name = left(t.name, 50) || ' (' || t.id || ')'
+WHERE EXISTS (SELECT 1 FROM teams x WHERE lower(x.name) = lower(t.name) AND x.id < t.id);
+
+CREATE UNIQUE INDEX teams_name_lower ON teams (lower(name));
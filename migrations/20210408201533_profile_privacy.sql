@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE tokens ADD COLUMN profile_public BOOLEAN NOT NULL DEFAULT true;
+ALTER TABLE tokens ADD COLUMN show_teams BOOLEAN NOT NULL DEFAULT true;
@@ -0,0 +1,14 @@
+-- Add migration script here
+-- Metadata of a mod edited after its upload, applied to every version of the mod. NULL columns keep
+-- the values of the versions, empty ones remove them.
+CREATE TABLE mod_metadata (
+    mod_name TEXT PRIMARY KEY,
+    description TEXT,
+    homepage TEXT,
+    documentation TEXT,
+    keywords TEXT[],
+    repository_git TEXT,
+    repository_hg TEXT,
+    updated TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_by BIGINT NOT NULL
+);
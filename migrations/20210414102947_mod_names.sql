@@ -0,0 +1,30 @@
+-- Add migration script here
+-- Every registered mod name, compared ignoring case, `-`, `_` and spaces.
+CREATE TABLE mod_names (
+    normalized TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Names that already collide stay with their mods, the oldest one is registered.
+INSERT INTO mod_names (normalized, name, created)
+    SELECT lower(regexp_replace(name, '[-_ ]', '', 'g')), name, MIN(uploaded)
+    FROM mods
+    GROUP BY name
+    ORDER BY MIN(uploaded)
+ON CONFLICT DO NOTHING;
+
+-- Names only Admins and Owners can register, for core mods.
+CREATE TABLE reserved_mod_names (
+    normalized TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    reserved_by BIGINT,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO reserved_mod_names (normalized, name) VALUES
+    ('polytech', 'PolyTech'),
+    ('polytechframework', 'PolyTech Framework'),
+    ('polymod', 'PolyMod'),
+    ('polybridge', 'Poly Bridge')
+ON CONFLICT DO NOTHING;
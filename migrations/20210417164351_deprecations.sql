@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE mod_deprecations (
+    mod_name TEXT PRIMARY KEY,
+    message TEXT NOT NULL,
+    successor TEXT,
+    deprecated_by BIGINT NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
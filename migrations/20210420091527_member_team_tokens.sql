@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Team tokens used to be shared by every member, so members who left could keep using them.
+-- Each member now gets their own, and the shared ones are revoked.
+INSERT INTO token_events (owner_id, token_id, token_name, event)
+    SELECT owner_id, id, name, 'revoked' FROM api_tokens WHERE name = 'team';
+
+DELETE FROM api_tokens WHERE name = 'team';
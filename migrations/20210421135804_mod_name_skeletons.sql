@@ -0,0 +1,29 @@
+-- Add migration script here
+-- The normalized name with the characters that look alike replaced, two names with the same
+-- skeleton are too similar to both be registered.
+CREATE FUNCTION mod_name_skeleton(name TEXT) RETURNS TEXT AS $$
+    SELECT
+        replace(replace(replace(replace(replace(replace(replace(replace(replace(replace(replace(
+            lower(regexp_replace(name, '[-_ ]', '', 'g')),
+            'rn', 'm'), 'vv', 'w'), 'cl', 'd'), '0', 'o'), '1', 'l'), 'i', 'l'),
+            '3', 'e'), '4', 'a'), '5', 's'), '7', 't'), '8', 'b')
+$$ LANGUAGE SQL IMMUTABLE;
+
+ALTER TABLE mod_names ADD COLUMN skeleton TEXT;
+
+-- Similar names registered before they were refused stay with their mods, only the oldest one
+-- gets the skeleton, which is enough to refuse new names like them.
+UPDATE mod_names n SET skeleton = mod_name_skeleton(n.name)
+    WHERE n.name IN (
+        SELECT DISTINCT ON (mod_name_skeleton(name)) name
+        FROM mod_names
+        ORDER BY mod_name_skeleton(name), created, name
+    );
+
+CREATE UNIQUE INDEX mod_names_skeleton ON mod_names (skeleton);
+
+ALTER TABLE reserved_mod_names ADD COLUMN skeleton TEXT;
+UPDATE reserved_mod_names SET skeleton = mod_name_skeleton(name);
+ALTER TABLE reserved_mod_names ALTER COLUMN skeleton SET NOT NULL;
+
+CREATE INDEX reserved_mod_names_skeleton ON reserved_mod_names (skeleton);
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- The level a version had before an override, so it can be restored when a mod stops being Core.
+ALTER TABLE verification_overrides ADD COLUMN previous_verification VERIFICATION_LEVEL;
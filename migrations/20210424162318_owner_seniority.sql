@@ -0,0 +1,15 @@
+-- Add migration script here
+-- When the owner got the mod, owners can only remove the owners added after them.
+ALTER TABLE owners ADD COLUMN added TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+-- Owners added through invites or reclaims get when that happened, the others when the mod was
+-- first uploaded.
+UPDATE owners o SET added = COALESCE(
+    (
+        SELECT MAX(a.created) FROM audit_log a
+        WHERE a.mod_name = o.mod_name AND a.target_id = o.owner_id
+        AND a.action IN ('add_owner', 'transfer_mod', 'reclaim_name')
+    ),
+    (SELECT MIN(m.uploaded) FROM mods m WHERE m.name = o.mod_name),
+    o.added
+);
@@ -0,0 +1,288 @@
+use crate::error::*;
+use crate::model::*;
+use crate::utils::sessions::mark_session_seen;
+use crate::utils::tokens::{hash_token, mark_token_used, request_ip};
+
+use actix_identity::RequestIdentity;
+use actix_web::dev::Payload;
+use actix_web::http::{header, Method};
+use actix_web::{web, FromRequest, HttpRequest};
+
+use futures::future::{self, FutureExt, LocalBoxFuture};
+use sqlx::PgPool;
+
+/// The account making a request, authenticated with either an API token or a cookie session.
+///
+/// Resolved once per request and cached in the request extensions, so it can be extracted by
+/// any number of handlers and helpers without querying the database again.
+/// Banned accounts are rejected by the extractor.
+///
+/// Browsers send the cookie with requests made by other sites, so cookie sessions only get the
+/// `read` scope on GET requests, and other requests must come from this site.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    /// The user id, or the team id if `is_team` is set.
+    pub id: i64,
+    pub is_team: bool,
+    pub roles: Roles,
+    pub is_banned: bool,
+    /// The id of the token used, `None` for cookie sessions.
+    pub token_id: Option<i32>,
+    /// The id of the session used, `None` for tokens.
+    pub session_id: Option<i32>,
+    /// The scopes of the token used. Cookie sessions only get `read` on GET and HEAD requests, and
+    /// every scope on other requests once they pass the `Origin`/`Referer` check.
+    pub scopes: Vec<Scope>,
+    /// Set for cookie sessions on GET and HEAD requests, which can't change anything.
+    pub read_only: bool,
+}
+
+impl Principal {
+    /// Returns an error meant to be sent to the client if the principal is missing the scope.
+    pub fn require_scope(&self, scope: Scope) -> ServiceResult<()> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(ServiceError::Forbidden(format!(
+                "This token is missing the `{}` scope.",
+                scope
+            )))
+        }
+    }
+
+    /// Checks if the principal can upload new versions of the mod, with either `publish` or
+    /// `publish:<mod>`.
+    pub fn can_publish(&self, mod_name: &str) -> bool {
+        self.scopes.iter().any(|i| match i {
+            Scope::Publish => true,
+            Scope::PublishMod(name) => name == mod_name,
+            _ => false,
+        })
+    }
+
+    /// Gets the id of the user, erroring for teams.
+    pub fn user_id(&self) -> ServiceResult<i64> {
+        if self.is_team {
+            Err(ServiceError::Forbidden(
+                "This action can't be done with a team token.".into(),
+            ))
+        } else {
+            Ok(self.id)
+        }
+    }
+
+    /// Errors for read only sessions, for actions that don't need any scope.
+    pub fn require_write(&self) -> ServiceResult<()> {
+        if self.read_only {
+            Err(ServiceError::Forbidden(
+                "This action can't be done with a GET request.".into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Errors unless the principal comes from a cookie session.
+    ///
+    /// Used for managing tokens, so a token can't be used to create tokens with more scopes.
+    pub fn require_session(&self) -> ServiceResult<()> {
+        self.require_write()?;
+
+        if self.token_id.is_none() {
+            Ok(())
+        } else {
+            Err(ServiceError::Forbidden(
+                "This action can only be done from the website.".into(),
+            ))
+        }
+    }
+}
+
+/// Finds the account of an API token, ignoring expired tokens.
+///
+/// The previous value of a rotated token is still accepted until its grace period ends.
+async fn from_token(token: &str, pool: &PgPool) -> ServiceResult<Option<Principal>> {
+    let query = sqlx::query!(
+        r#"
+            SELECT
+                api_tokens.id,
+                api_tokens.owner_id,
+                api_tokens.scopes,
+                tokens.roles,
+                tokens.is_team,
+                (
+                    tokens.is_banned
+                    AND (tokens.ban_expires IS NULL OR tokens.ban_expires > CURRENT_TIMESTAMP)
+                ) as "is_banned!"
+            FROM
+                api_tokens
+                JOIN tokens ON tokens.owner_id = api_tokens.owner_id
+            WHERE
+                (
+                    api_tokens.token_hash = $1
+                    OR (
+                        api_tokens.previous_token_hash = $1
+                        AND api_tokens.previous_expires > CURRENT_TIMESTAMP
+                    )
+                )
+                AND (api_tokens.expires IS NULL OR api_tokens.expires > CURRENT_TIMESTAMP)
+        "#,
+        &hash_token(token),
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(query.map(|data| Principal {
+        id: data.owner_id,
+        is_team: data.is_team,
+        roles: Roles::from_bits_truncate(data.roles as u32),
+        is_banned: data.is_banned,
+        token_id: Some(data.id),
+        session_id: None,
+        scopes: data.scopes.iter().filter_map(|i| i.parse().ok()).collect(),
+        read_only: false,
+    }))
+}
+
+/// Finds the account of the session in the cookie, ignoring expired sessions.
+async fn from_session(
+    session: &str,
+    read_only: bool,
+    pool: &PgPool,
+) -> ServiceResult<Option<Principal>> {
+    let query = sqlx::query!(
+        r#"
+            SELECT
+                sessions.id,
+                sessions.user_id,
+                tokens.roles,
+                tokens.is_team,
+                (
+                    tokens.is_banned
+                    AND (tokens.ban_expires IS NULL OR tokens.ban_expires > CURRENT_TIMESTAMP)
+                ) as "is_banned!"
+            FROM
+                sessions
+                JOIN tokens ON tokens.owner_id = sessions.user_id
+            WHERE
+                sessions.session_hash = $1
+                AND sessions.expires > CURRENT_TIMESTAMP
+                AND NOT tokens.is_team
+        "#,
+        hash_token(session),
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(query.map(|data| Principal {
+        id: data.user_id,
+        is_team: data.is_team,
+        roles: Roles::from_bits_truncate(data.roles as u32),
+        is_banned: data.is_banned,
+        token_id: None,
+        session_id: Some(data.id),
+        scopes: if read_only {
+            vec![Scope::Read]
+        } else {
+            Scope::all()
+        },
+        read_only,
+    }))
+}
+
+/// Checks that a request was made by this site, from its `Origin` or `Referer` header.
+fn check_origin(req: &HttpRequest) -> ServiceResult<()> {
+    let headers = req.headers();
+    let origin = headers
+        .get(header::ORIGIN)
+        .or_else(|| headers.get(header::REFERER))
+        .and_then(|i| i.to_str().ok())
+        .and_then(|i| reqwest::Url::parse(i).ok());
+
+    let conn = req.connection_info();
+    let host = conn.host();
+
+    let is_same_origin = match origin {
+        Some(url) => match (url.host_str(), url.port()) {
+            (Some(x), Some(port)) => format!("{}:{}", x, port) == host,
+            (Some(x), None) => x == host,
+            _ => false,
+        },
+        None => false,
+    };
+
+    if is_same_origin {
+        Ok(())
+    } else {
+        Err(ServiceError::Forbidden(
+            "Cross-site requests are not allowed.".into(),
+        ))
+    }
+}
+
+/// Resolves the principal of a request, preferring the `Authorization` header over the cookie.
+async fn resolve(req: &HttpRequest) -> ServiceResult<Principal> {
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .cloned()
+        .ok_or_else(|| ServiceError::InternalServerError("Missing database pool".into()))?;
+
+    let ip = request_ip(req);
+
+    let principal = if let Some(token) = req.headers().get("Authorization") {
+        let token = token.to_str().map_err(|_| ServiceError::Unauthorized)?;
+        let principal = from_token(token, &pool).await?;
+
+        if let Some(ref x) = principal {
+            // unwrap is safe, token principals always have a token id.
+            mark_token_used(x.token_id.unwrap(), ip.as_deref(), &pool).await?;
+        }
+
+        principal
+    } else if let Some(session) = req.get_identity() {
+        let read_only = matches!(*req.method(), Method::GET | Method::HEAD);
+
+        if !read_only {
+            check_origin(req)?;
+        }
+
+        let principal = from_session(&session, read_only, &pool).await?;
+
+        if let Some(ref x) = principal {
+            // unwrap is safe, session principals always have a session id.
+            mark_session_seen(x.session_id.unwrap(), ip.as_deref(), &pool).await?;
+        }
+
+        principal
+    } else {
+        None
+    };
+
+    match principal {
+        Some(x) if x.is_banned => Err(ServiceError::Forbidden("Banned User".into())),
+        Some(x) => Ok(x),
+        None => Err(ServiceError::Unauthorized),
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = ServiceError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Some(principal) = req.extensions().get::<Principal>() {
+            return future::ok(principal.clone()).boxed_local();
+        }
+
+        let req = req.clone();
+
+        async move {
+            let principal = resolve(&req).await?;
+            req.extensions_mut().insert(principal.clone());
+
+            Ok(principal)
+        }
+        .boxed_local()
+    }
+}
@@ -11,6 +11,7 @@ pub enum ServiceError {
     BadRequest(String),
     InternalServerError(String),
     Unauthorized,
+    Forbidden(String),
     NoContent,
     Timeout,
 }
@@ -31,6 +32,7 @@ impl ResponseError for ServiceError {
                 HttpResponse::InternalServerError().body(message)
             }
             ServiceError::Unauthorized => HttpResponse::Unauthorized().body("Unauthorized"),
+            ServiceError::Forbidden(ref message) => HttpResponse::Forbidden().body(message),
             ServiceError::NoContent => HttpResponse::NoContent().finish(),
             ServiceError::Timeout => HttpResponse::RequestTimeout().finish(),
         }
@@ -96,3 +98,10 @@ impl From<reqwest::Error> for ServiceError {
         }
     }
 }
+
+impl From<darkredis::Error> for ServiceError {
+    fn from(err: darkredis::Error) -> ServiceError {
+        error!("Redis Error: {}", err);
+        ServiceError::InternalServerError("A handled cache error has happened".into())
+    }
+}
@@ -9,25 +9,29 @@ extern crate tracing;
 #[macro_use]
 extern crate bitflags;
 
+pub mod auth;
 pub mod error;
 pub mod model;
+pub mod providers;
 pub mod routes;
 pub mod utils;
 
 use crate::model::*;
+use crate::providers::Providers;
 use crate::routes::*;
+use crate::utils::sessions::SESSION_DAYS;
 
 use std::env;
 
 use actix_files::NamedFile;
 use actix_identity::{CookieIdentityPolicy, IdentityService};
 use actix_ratelimit::{RateLimiter, RedisStore, RedisStoreActor};
-use actix_web::dev::{Service, ServiceRequest};
+use actix_web::dev::ServiceRequest;
 use actix_web::{middleware, web, App, HttpResponse, HttpServer};
 
 use darkredis::ConnectionPool;
 use handlebars::Handlebars;
-use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::postgres::PgPoolOptions;
 use time::Duration;
 use toml::Value;
 
@@ -81,6 +85,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let config_ref = web::Data::new(config.clone());
 
+    // Identity Providers
+    let providers = Providers::from_config(&config).await?;
+    let providers_ref = web::Data::new(providers);
+
     // Handlebars for templating.
     let mut handlebars = Handlebars::new();
     handlebars.register_templates_directory(".html.hbs", "./templates")?;
@@ -101,6 +109,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let db_ref = web::Data::new(db.clone());
 
     let secret_key = config.secret_key.clone();
+    let secure_cookies = config.secure_cookies;
+    let same_site = config.same_site;
 
     info!("Binding to http://{}:{}", &config.address, &config.port);
 
@@ -110,13 +120,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 CookieIdentityPolicy::new(&hex::decode(&secret_key).unwrap())
                     .name("auth")
                     .path("/")
-                    .max_age_time(Duration::days(1))
-                    .secure(false),
+                    .max_age_time(Duration::days(SESSION_DAYS.into()))
+                    .secure(secure_cookies)
+                    .same_site(same_site.into()),
             ))
             .app_data(handlebars_ref.clone())
             .app_data(redis_ref.clone())
             .app_data(db_ref.clone())
             .app_data(config_ref.clone())
+            .app_data(providers_ref.clone())
             .wrap(
                 // TODO: https://github.com/TerminalWitchcraft/actix-ratelimit/issues/10
                 RateLimiter::new(RedisStoreActor::from(store.clone()).start())
@@ -140,11 +152,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             ))
             .service(web::resource("/mod").route(web::get().to(get_mod::front_end)))
             .service(web::resource("/user").route(web::get().to(users::index)))
-            .service(web::resource("/oauth2_url").to(login::get_oauth2))
+            .service(web::resource("/users/{id}").route(web::get().to(users::profile_page)))
+            .service(web::resource("/teams/join/{invite}").route(web::get().to(teams::join_page)))
+            .service(web::resource("/teams/{id}").route(web::get().to(teams::team_page)))
+            .service(web::resource("/providers").route(web::get().to(login::providers)))
             .service(web::resource("/login").route(web::get().to(login::login)))
+            .service(web::resource("/login/{provider}").route(web::get().to(login::provider_login)))
             .service(web::resource("/logout").to(login::logout))
-            .service(web::resource("/token").route(web::get().to(login::get_token)))
-            .service(web::resource("/discord/oauth2").route(web::get().to(login::oauth)))
+            .service(web::resource("/token").route(web::post().to(tokens::create)))
+            .service(web::resource("/oauth2/{provider}").route(web::get().to(login::oauth)))
+            .service(web::resource("/discord/oauth2").route(web::get().to(login::discord_oauth)))
             .service(actix_files::Files::new("/static", "./static").show_files_listing())
             .service(actix_files::Files::new("/templates", "./templates").show_files_listing())
             .service(
@@ -159,64 +176,185 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     )
                     .service(web::resource("/get_mod").route(web::get().to(get_mod::get_mod)))
                     .service(web::resource("/search").route(web::get().to(search::search)))
+                    .service(web::resource("/core_mods").route(web::get().to(admin::core_mods)))
+                    .service(
+                        web::resource("/advisories")
+                            .route(web::get().to(advisories::list))
+                            .route(web::post().to(advisories::check)),
+                    )
                     .service(web::resource("/me").route(web::get().to(users::me)))
+                    .service(
+                        web::scope("/users")
+                            .service(
+                                web::resource("/privacy").route(web::post().to(users::set_privacy)),
+                            )
+                            .service(
+                                web::resource("/{id}").route(web::get().to(users::public_user)),
+                            ),
+                    )
+                    .service(
+                        web::scope("/owners")
+                            .service(web::resource("").route(web::get().to(owners::list)))
+                            .service(web::resource("/invite").route(web::post().to(owners::invite)))
+                            .service(
+                                web::resource("/invites").route(web::get().to(owners::invites)),
+                            )
+                            .service(web::resource("/accept").route(web::post().to(owners::accept)))
+                            .service(
+                                web::resource("/decline").route(web::post().to(owners::decline)),
+                            )
+                            .service(
+                                web::resource("/remove").route(web::post().to(owners::remove)),
+                            ),
+                    )
+                    .service(
+                        web::scope("/sessions")
+                            .service(web::resource("").route(web::get().to(sessions::list)))
+                            .service(
+                                web::resource("/revoke").route(web::post().to(sessions::revoke)),
+                            )
+                            .service(
+                                web::resource("/revoke_others")
+                                    .route(web::post().to(sessions::revoke_others)),
+                            ),
+                    )
+                    .service(
+                        web::scope("/accounts")
+                            .service(web::resource("").route(web::get().to(login::linked_accounts)))
+                            .service(
+                                web::resource("/unlink")
+                                    .route(web::post().to(login::unlink_account)),
+                            ),
+                    )
+                    .service(
+                        web::scope("/tokens")
+                            .service(web::resource("").route(web::get().to(tokens::list)))
+                            .service(web::resource("/create").route(web::post().to(tokens::create)))
+                            .service(web::resource("/revoke").route(web::post().to(tokens::revoke)))
+                            .service(web::resource("/rotate").route(web::post().to(tokens::rotate)))
+                            .service(web::resource("/events").route(web::get().to(tokens::events))),
+                    )
                     .service(
                         web::scope("/teams")
-                            .service(web::resource("/token").route(web::get().to(teams::get_token)))
-                            .service(web::resource("/invite").route(web::get().to(teams::invite)))
+                            .service(
+                                web::resource("/token").route(web::post().to(teams::get_token)),
+                            )
+                            .service(
+                                web::resource("/token/rotate")
+                                    .route(web::post().to(teams::rotate_team_token)),
+                            )
+                            .service(web::resource("/invite").route(web::post().to(teams::invite)))
+                            .service(web::resource("/invites").route(web::get().to(teams::invites)))
+                            .service(
+                                web::resource("/invites/revoke")
+                                    .route(web::post().to(teams::revoke_invite)),
+                            )
                             .service(
                                 web::resource("/create").route(web::post().to(teams::create_team)),
                             )
                             .service(
                                 web::resource("/transfer_mod")
-                                    .route(web::get().to(teams::transfer_mod)),
+                                    .route(web::post().to(teams::transfer_mod)),
+                            )
+                            .service(
+                                web::resource("/join/{invite}").route(web::post().to(teams::join)),
+                            )
+                            .service(web::resource("/members").route(web::get().to(teams::members)))
+                            .service(web::resource("/kick").route(web::post().to(teams::kick)))
+                            .service(web::resource("/leave").route(web::post().to(teams::leave)))
+                            .service(web::resource("/role").route(web::post().to(teams::set_role)))
+                            .service(
+                                web::resource("/transfer")
+                                    .route(web::post().to(teams::transfer_ownership)),
+                            )
+                            .service(
+                                web::resource("/delete").route(web::post().to(teams::delete_team)),
                             )
                             .service(
-                                web::resource("/join/{invite}").route(web::get().to(teams::join)),
+                                web::resource("/mod_permissions")
+                                    .route(web::get().to(teams::mod_permissions_list))
+                                    .route(web::post().to(teams::set_mod_permissions)),
+                            )
+                            .service(web::resource("/edit").route(web::post().to(teams::edit_team)))
+                            .service(
+                                web::resource("/{id}").route(web::get().to(teams::public_team)),
                             ),
                     ),
             )
             .service(
                 web::scope("/api")
-                    .wrap_fn(|req, srv| {
-                        let db = req.app_data::<web::Data<PgPool>>().cloned().unwrap();
-                        let token = match &req.headers().get("Authorization") {
-                            Some(x) => x.to_str().unwrap().to_string(),
-                            None => String::new(),
-                        };
-
-                        let fut = srv.call(req);
-
-                        async move {
-                            if token.is_empty() {
-                                return Err(HttpResponse::Unauthorized()
-                                    .body("No Authorization Token provided")
-                                    .into());
-                            }
-
-                            let query = sqlx::query!(
-                                "SELECT is_banned FROM tokens WHERE token = $1",
-                                &token,
-                            )
-                            .fetch_optional(&**db.clone())
-                            .await
-                            .unwrap();
-
-                            if let Some(data) = query {
-                                if data.is_banned {
-                                    Err(HttpResponse::Unauthorized().body("Banned User").into())
-                                } else {
-                                    let res = fut.await?;
-                                    Ok(res)
-                                }
-                            } else {
-                                Err(HttpResponse::Unauthorized().body("Invalid Token").into())
-                            }
-                        }
-                    })
                     .service(web::resource("/upload").route(web::post().to(upload::upload)))
                     .service(web::resource("/verify").to(verification::verify))
-                    .service(web::resource("/yank").to(verification::yank)),
+                    .service(web::resource("/verify/retract").to(verification::retract))
+                    .service(web::resource("/yank").to(verification::yank))
+                    .service(web::resource("/unyank").to(verification::unyank))
+                    .service(web::resource("/metadata").route(web::post().to(metadata::edit)))
+                    .service(
+                        web::resource("/deprecate").route(web::post().to(deprecation::deprecate)),
+                    )
+                    .service(
+                        web::resource("/undeprecate")
+                            .route(web::post().to(deprecation::undeprecate)),
+                    )
+                    .service(
+                        web::scope("/tokens")
+                            .service(web::resource("").route(web::get().to(tokens::list)))
+                            .service(web::resource("/revoke").to(tokens::api_revoke))
+                            .service(web::resource("/rotate").to(tokens::api_rotate))
+                            .service(web::resource("/events").to(tokens::events)),
+                    )
+                    .service(
+                        web::scope("/advisories")
+                            .service(
+                                web::resource("/create").route(web::post().to(advisories::create)),
+                            )
+                            .service(web::resource("/withdraw").to(advisories::withdraw)),
+                    )
+                    .service(
+                        web::scope("/admin")
+                            .service(web::resource("/core").route(web::post().to(admin::core)))
+                            .service(
+                                web::resource("/unsafe").route(web::post().to(admin::force_unsafe)),
+                            )
+                            .service(
+                                web::resource("/override")
+                                    .route(web::post().to(admin::override_verification)),
+                            )
+                            .service(web::resource("/roles").route(web::post().to(admin::set_role)))
+                            .service(web::resource("/ban").route(web::post().to(admin::ban)))
+                            .service(web::resource("/unban").route(web::post().to(admin::unban)))
+                            .service(web::resource("/banned").to(admin::banned))
+                            .service(web::resource("/audit").to(admin::audit_log))
+                            .service(
+                                web::scope("/blocklist")
+                                    .service(web::resource("").to(admin::blocklist))
+                                    .service(
+                                        web::resource("/add").route(web::post().to(admin::block)),
+                                    )
+                                    .service(
+                                        web::resource("/remove")
+                                            .route(web::post().to(admin::unblock)),
+                                    )
+                                    .service(
+                                        web::resource("/hash_files")
+                                            .route(web::post().to(admin::hash_old_files)),
+                                    ),
+                            )
+                            .service(
+                                web::scope("/reserved")
+                                    .service(web::resource("").to(admin::reserved_names))
+                                    .service(
+                                        web::resource("/add").route(web::post().to(admin::reserve)),
+                                    )
+                                    .service(
+                                        web::resource("/remove")
+                                            .route(web::post().to(admin::unreserve)),
+                                    ),
+                            )
+                            .service(
+                                web::resource("/reclaim").route(web::post().to(admin::reclaim)),
+                            ),
+                    ),
             )
             .default_service(web::to(|| {
                 HttpResponse::NotFound().body("404 - Route Not Found")
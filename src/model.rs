@@ -1,5 +1,8 @@
+use actix_web::cookie::SameSite;
 use serde_aux::prelude::*;
+use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
 
 pub const API_ENDPOINT: &str = "https://discord.com/api/v8";
 pub const HEX_BASE: [&str; 16] = [
@@ -9,6 +12,7 @@ pub const HEX_BASE: [&str; 16] = [
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, sqlx::Type)]
 pub enum Verification {
     None,
+    /// No longer assigned, yanking is tracked by `mods.yanked` so the verification level is kept.
     Yanked,
     Unsafe,
     Auto,
@@ -35,23 +39,113 @@ impl fmt::Display for Verification {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, sqlx::Type)]
+#[sqlx(rename = "advisory_severity")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Low => write!(f, "Low"),
+            Self::Medium => write!(f, "Medium"),
+            Self::High => write!(f, "High"),
+            Self::Critical => write!(f, "Critical"),
+        }
+    }
+}
+
+/// A permission granted to an API token.
+///
+/// Scopes only narrow what a token can do, the roles of its owner are still checked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scope {
+    /// Upload new versions of any mod the owner can publish.
+    Publish,
+    /// Upload new versions of a single mod.
+    PublishMod(String),
+    Yank,
+    Verify,
+    Read,
+    /// Use the admin endpoints.
+    Admin,
+}
+
+impl Scope {
+    /// Every scope that isn't restricted to a single mod, given to the sessions of logged in users.
+    pub fn all() -> Vec<Scope> {
+        vec![
+            Self::Publish,
+            Self::Yank,
+            Self::Verify,
+            Self::Read,
+            Self::Admin,
+        ]
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Publish => write!(f, "publish"),
+            Self::PublishMod(name) => write!(f, "publish:{}", name),
+            Self::Yank => write!(f, "yank"),
+            Self::Verify => write!(f, "verify"),
+            Self::Read => write!(f, "read"),
+            Self::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "publish" => Ok(Self::Publish),
+            "yank" => Ok(Self::Yank),
+            "verify" => Ok(Self::Verify),
+            "read" => Ok(Self::Read),
+            "admin" => Ok(Self::Admin),
+            _ => match s.strip_prefix("publish:") {
+                Some(name) if !name.is_empty() => Ok(Self::PublishMod(name.to_string())),
+                _ => Err(format!("Unknown scope `{}`", s)),
+            },
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct OAuthTokenData {
-    pub client_id: u64,
+    pub client_id: String,
     pub client_secret: String,
     pub grant_type: String,
     pub code: String,
     pub redirect_uri: String,
-    pub scope: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OAuthRefreshData {
+    pub client_id: String,
+    pub client_secret: String,
+    pub grant_type: String,
+    pub refresh_token: String,
+}
+
+/// Response of the token endpoint of a provider.
+///
+/// Only `access_token` is required, as GitHub doesn't expire its tokens.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct OAuthResponse {
     pub access_token: String,
-    pub expires_in: u32,
-    pub refresh_token: String,
-    pub scope: String,
-    pub token_type: String,
+    pub expires_in: Option<u32>,
+    pub refresh_token: Option<String>,
+    pub scope: Option<String>,
+    pub token_type: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -67,7 +161,7 @@ pub struct UserResponse {
     pub locale: String,
     pub mfa_enabled: bool,
     pub premium_type: Option<u64>,
-    pub email: String,
+    pub email: Option<String>,
 }
 
 fn default_address() -> String {
@@ -108,13 +202,20 @@ pub struct Config {
 
     // Private Cookies
     pub secret_key: String,
-    pub iv_key: String,
+    /// Only send the cookies over HTTPS, should be enabled when served behind HTTPS.
+    #[serde(default)]
+    pub secure_cookies: bool,
+    #[serde(default)]
+    pub same_site: CookieSameSite,
 
-    // Discord
-    pub oauth2_url: String,
-    pub client_id: u64,
-    pub client_secret: String,
-    pub redirect_uri: String,
+    // Identity Providers
+    #[serde(default)]
+    pub providers: HashMap<String, ProviderConfig>,
+
+    // Discord, from before `providers` existed. Used as the `discord` provider if it's missing.
+    pub client_id: Option<u64>,
+    pub client_secret: Option<String>,
+    pub redirect_uri: Option<String>,
 
     // Other
     #[serde(default = "default_redis")]
@@ -123,6 +224,49 @@ pub struct Config {
     pub mods_path: String,
 }
 
+/// The `SameSite` attribute of the cookies, `none` requires `secure_cookies`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSameSite {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+impl From<CookieSameSite> for SameSite {
+    fn from(value: CookieSameSite) -> SameSite {
+        match value {
+            CookieSameSite::Strict => SameSite::Strict,
+            CookieSameSite::Lax => SameSite::Lax,
+            CookieSameSite::None => SameSite::None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Discord,
+    GitHub,
+    Oidc,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProviderConfig {
+    /// Defaults to the name of the provider, so `[providers.github]` doesn't need it.
+    pub kind: Option<ProviderKind>,
+    /// Name shown on the login page.
+    pub display_name: Option<String>,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Must be `<address>/oauth2/<provider name>`.
+    pub redirect_uri: String,
+    /// OpenID Connect only, the discovery document is fetched from
+    /// `<issuer>/.well-known/openid-configuration`.
+    pub issuer: Option<String>,
+}
+
 bitflags! {
     pub struct Roles: u32 {
         const OWNER    = 0b00000001;
@@ -140,6 +284,49 @@ impl Default for Roles {
     }
 }
 
+impl Roles {
+    /// Position in the staff hierarchy, staff can only manage accounts below their own.
+    pub fn rank(self) -> u8 {
+        if self.contains(Roles::OWNER) {
+            3
+        } else if self.contains(Roles::ADMIN) {
+            2
+        } else if self.contains(Roles::MOD) {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// The roles someone with these roles can grant and revoke.
+    ///
+    /// Owners can grant every role, Admins every role below Admin, and Mods none.
+    pub fn grantable(self) -> Roles {
+        match self.rank() {
+            3 => Roles::all(),
+            2 => Roles::MOD | Roles::VERIFIER | Roles::MAPPER | Roles::BOT,
+            _ => Roles::empty(),
+        }
+    }
+}
+
+impl FromStr for Roles {
+    type Err = String;
+
+    /// Parses the name of a single role, case insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "OWNER" => Ok(Roles::OWNER),
+            "ADMIN" => Ok(Roles::ADMIN),
+            "MOD" => Ok(Roles::MOD),
+            "VERIFIER" => Ok(Roles::VERIFIER),
+            "MAPPER" => Ok(Roles::MAPPER),
+            "BOT" => Ok(Roles::BOT),
+            _ => Err(format!("Unknown role `{}`", s)),
+        }
+    }
+}
+
 bitflags! {
     pub struct TeamRoles: u32 {
         const OWNER    = 0b00000001;
@@ -153,3 +340,74 @@ impl Default for TeamRoles {
         TeamRoles::from_bits_truncate(0)
     }
 }
+
+impl TeamRoles {
+    /// What members with these roles can do with the mods of the team, unless set for the mod.
+    pub fn mod_permissions(self) -> ModPermissions {
+        match self.rank() {
+            3 | 2 => ModPermissions::all(),
+            1 => ModPermissions::PUBLISH | ModPermissions::YANK,
+            _ => ModPermissions::empty(),
+        }
+    }
+
+    /// Position in the team hierarchy, members with no roles are 0.
+    pub fn rank(self) -> u8 {
+        if self.contains(TeamRoles::OWNER) {
+            3
+        } else if self.contains(TeamRoles::ADMIN) {
+            2
+        } else if self.contains(TeamRoles::MOD) {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+impl FromStr for TeamRoles {
+    type Err = String;
+
+    /// Parses the role a member can be given, case insensitive. `member` is no roles.
+    ///
+    /// Owner is not included, as ownership is transferred instead.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "ADMIN" => Ok(TeamRoles::ADMIN),
+            "MOD" => Ok(TeamRoles::MOD),
+            "MEMBER" => Ok(TeamRoles::empty()),
+            _ => Err(format!("Unknown team role `{}`", s)),
+        }
+    }
+}
+
+bitflags! {
+    /// What someone can do with a mod they own, or that a team they are in owns.
+    pub struct ModPermissions: u32 {
+        /// Upload new versions.
+        const PUBLISH  = 0b00000001;
+        const YANK     = 0b00000010;
+        /// Set the permissions of the team members over the mod.
+        const MANAGE   = 0b00000100;
+    }
+}
+
+impl FromStr for ModPermissions {
+    type Err = String;
+
+    /// Parses a comma separated list of permissions, case insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut permissions = ModPermissions::empty();
+
+        for i in s.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            permissions |= match i.to_uppercase().as_str() {
+                "PUBLISH" => ModPermissions::PUBLISH,
+                "YANK" => ModPermissions::YANK,
+                "MANAGE" => ModPermissions::MANAGE,
+                _ => return Err(format!("Unknown permission `{}`", i)),
+            };
+        }
+
+        Ok(permissions)
+    }
+}
@@ -0,0 +1,309 @@
+use crate::error::*;
+use crate::model::*;
+
+use reqwest::header;
+use reqwest::Url;
+
+/// The account of a user on an identity provider.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExternalProfile {
+    /// Unique id of the account on the provider, as a string as every provider has its own format.
+    pub id: String,
+    pub username: String,
+    /// Discord only.
+    pub discriminator: Option<String>,
+    pub email: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OidcDiscovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubUser {
+    id: u64,
+    login: String,
+    email: Option<String>,
+    avatar_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OidcUserInfo {
+    sub: String,
+    preferred_username: Option<String>,
+    name: Option<String>,
+    email: Option<String>,
+    picture: Option<String>,
+}
+
+/// An OAuth2 identity provider users can log in with.
+#[derive(Debug, Clone)]
+pub struct Provider {
+    /// Name used in the urls and in `linked_accounts`.
+    pub name: String,
+    pub display_name: String,
+    pub kind: ProviderKind,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+impl Provider {
+    /// Sets up a provider, fetching the discovery document of OpenID Connect providers.
+    pub async fn new(name: &str, config: &ProviderConfig) -> ServiceResult<Provider> {
+        let kind = match config.kind {
+            Some(x) => x,
+            None => match name {
+                "discord" => ProviderKind::Discord,
+                "github" => ProviderKind::GitHub,
+                _ => ProviderKind::Oidc,
+            },
+        };
+
+        let (authorization_endpoint, token_endpoint, userinfo_endpoint) = match kind {
+            ProviderKind::Discord => (
+                "https://discord.com/api/oauth2/authorize".to_string(),
+                format!("{}/oauth2/token", API_ENDPOINT),
+                format!("{}/users/@me", API_ENDPOINT),
+            ),
+            ProviderKind::GitHub => (
+                "https://github.com/login/oauth/authorize".to_string(),
+                "https://github.com/login/oauth/access_token".to_string(),
+                "https://api.github.com/user".to_string(),
+            ),
+            ProviderKind::Oidc => {
+                let issuer = config.issuer.as_ref().ok_or_else(|| {
+                    ServiceError::InternalServerError(format!(
+                        "The `{}` OpenID Connect provider is missing an issuer.",
+                        name
+                    ))
+                })?;
+
+                let discovery = reqwest::get(&format!(
+                    "{}/.well-known/openid-configuration",
+                    issuer.trim_end_matches('/')
+                ))
+                .await?
+                .json::<OidcDiscovery>()
+                .await?;
+
+                (
+                    discovery.authorization_endpoint,
+                    discovery.token_endpoint,
+                    discovery.userinfo_endpoint,
+                )
+            }
+        };
+
+        let display_name = match (&config.display_name, kind) {
+            (Some(x), _) => x.to_string(),
+            (None, ProviderKind::Discord) => "Discord".to_string(),
+            (None, ProviderKind::GitHub) => "GitHub".to_string(),
+            (None, ProviderKind::Oidc) => name.to_string(),
+        };
+
+        Ok(Provider {
+            name: name.to_string(),
+            display_name,
+            kind,
+            client_id: config.client_id.to_string(),
+            client_secret: config.client_secret.to_string(),
+            redirect_uri: config.redirect_uri.to_string(),
+            authorization_endpoint,
+            token_endpoint,
+            userinfo_endpoint,
+        })
+    }
+
+    fn scope(&self) -> &'static str {
+        match self.kind {
+            ProviderKind::Discord => "identify email",
+            ProviderKind::GitHub => "read:user user:email",
+            ProviderKind::Oidc => "openid profile email",
+        }
+    }
+
+    /// The url users are sent to for logging in, `state` is sent back to the redirect uri.
+    pub fn authorize_url(&self, state: &str) -> ServiceResult<String> {
+        let url = Url::parse_with_params(
+            &self.authorization_endpoint,
+            &[
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("response_type", "code"),
+                ("scope", self.scope()),
+                ("state", state),
+            ],
+        )
+        .map_err(|why| ServiceError::InternalServerError(why.to_string()))?;
+
+        Ok(url.to_string())
+    }
+
+    /// Exchanges the code the provider redirected the user with for an access token.
+    pub async fn exchange_code(&self, code: &str) -> ServiceResult<OAuthResponse> {
+        let data = OAuthTokenData {
+            client_id: self.client_id.to_string(),
+            client_secret: self.client_secret.to_string(),
+            grant_type: "authorization_code".to_string(),
+            code: code.to_string(),
+            redirect_uri: self.redirect_uri.to_string(),
+        };
+
+        let client = reqwest::Client::new();
+        let resp = client
+            .post(&self.token_endpoint)
+            .header(header::ACCEPT, "application/json")
+            .form(&data)
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|_| ServiceError::BadRequest("Invalid or expired OAuth2 code.".into()))?
+            .json::<OAuthResponse>()
+            .await?;
+
+        Ok(resp)
+    }
+
+    /// Gets a new access token, for when the one stored expired.
+    pub async fn refresh(&self, refresh_token: &str) -> ServiceResult<OAuthResponse> {
+        let data = OAuthRefreshData {
+            client_id: self.client_id.to_string(),
+            client_secret: self.client_secret.to_string(),
+            grant_type: "refresh_token".to_string(),
+            refresh_token: refresh_token.to_string(),
+        };
+
+        let client = reqwest::Client::new();
+        let resp = client
+            .post(&self.token_endpoint)
+            .header(header::ACCEPT, "application/json")
+            .form(&data)
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|_| ServiceError::BadRequest("OAuth2 Session Expired".into()))?
+            .json::<OAuthResponse>()
+            .await?;
+
+        Ok(resp)
+    }
+
+    /// Gets the account of the user the access token belongs to.
+    pub async fn get_profile(&self, access_token: &str) -> ServiceResult<ExternalProfile> {
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(&self.userinfo_endpoint)
+            .bearer_auth(access_token)
+            // GitHub rejects requests without an user agent.
+            .header(header::USER_AGENT, "PolyMod")
+            .header(header::ACCEPT, "application/json")
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|_| ServiceError::BadRequest("OAuth2 Session Expired".into()))?;
+
+        let profile = match self.kind {
+            ProviderKind::Discord => {
+                let user = resp.json::<UserResponse>().await?;
+
+                let id = user.id;
+
+                ExternalProfile {
+                    avatar_url: user.avatar.map(|avatar| {
+                        format!("https://cdn.discordapp.com/avatars/{}/{}.png", id, avatar)
+                    }),
+                    id: id.to_string(),
+                    username: user.username,
+                    discriminator: Some(format!("{:04}", user.discriminator)),
+                    email: user.email,
+                }
+            }
+            ProviderKind::GitHub => {
+                let user = resp.json::<GitHubUser>().await?;
+
+                ExternalProfile {
+                    id: user.id.to_string(),
+                    username: user.login,
+                    discriminator: None,
+                    email: user.email,
+                    avatar_url: user.avatar_url,
+                }
+            }
+            ProviderKind::Oidc => {
+                let user = resp.json::<OidcUserInfo>().await?;
+
+                let id = user.sub;
+
+                ExternalProfile {
+                    username: user
+                        .preferred_username
+                        .or(user.name)
+                        .unwrap_or_else(|| id.to_string()),
+                    id,
+                    discriminator: None,
+                    email: user.email,
+                    avatar_url: user.picture,
+                }
+            }
+        };
+
+        Ok(profile)
+    }
+}
+
+/// Every identity provider configured, sorted by name.
+#[derive(Debug, Clone, Default)]
+pub struct Providers(Vec<Provider>);
+
+impl Providers {
+    /// Sets up the providers in the config.
+    ///
+    /// The old `client_id`, `client_secret` and `redirect_uri` keys are used as the `discord`
+    /// provider when it is not configured in `providers`.
+    pub async fn from_config(config: &Config) -> ServiceResult<Providers> {
+        let mut configs = config.providers.clone();
+
+        if let (Some(client_id), Some(client_secret), Some(redirect_uri)) = (
+            config.client_id,
+            &config.client_secret,
+            &config.redirect_uri,
+        ) {
+            configs
+                .entry("discord".to_string())
+                .or_insert_with(|| ProviderConfig {
+                    kind: Some(ProviderKind::Discord),
+                    display_name: None,
+                    client_id: client_id.to_string(),
+                    client_secret: client_secret.to_string(),
+                    redirect_uri: redirect_uri.to_string(),
+                    issuer: None,
+                });
+        }
+
+        let mut providers = Vec::new();
+
+        for (name, provider_config) in &configs {
+            providers.push(Provider::new(name, provider_config).await?);
+        }
+
+        providers.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Providers(providers))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Provider> {
+        self.0.iter().find(|i| i.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Provider> {
+        self.0.iter()
+    }
+}
@@ -0,0 +1,1227 @@
+use crate::auth::Principal;
+use crate::error::*;
+use crate::model::*;
+use crate::routes::verification::update_verification;
+use crate::utils::archive::{archive_path, hash_files};
+use crate::utils::audit::AuditEntry;
+use crate::utils::names::{normalize, MAX_NAME_LENGTH};
+use crate::utils::ownership::create_team_account;
+use actix_web::{web, HttpResponse};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+
+/// Days without a new version after which a mod can be reclaimed.
+const ABANDONED_DAYS: i32 = 365;
+
+/// Gets the id of the principal, if they are an Admin or an Owner.
+pub fn get_admin(principal: &Principal) -> ServiceResult<i64> {
+    principal.require_scope(Scope::Admin)?;
+
+    if principal.roles.intersects(Roles::ADMIN | Roles::OWNER) {
+        Ok(principal.id)
+    } else {
+        Err(ServiceError::Unauthorized)
+    }
+}
+
+/// Gets the id of the principal, if they are a Mod, an Admin or an Owner.
+pub fn get_moderator(principal: &Principal) -> ServiceResult<i64> {
+    principal.require_scope(Scope::Admin)?;
+
+    if principal
+        .roles
+        .intersects(Roles::OWNER | Roles::ADMIN | Roles::MOD)
+    {
+        Ok(principal.id)
+    } else {
+        Err(ServiceError::Unauthorized)
+    }
+}
+
+/// Gets the roles of an account, and if it's a team.
+async fn get_account(id: i64, pool: &PgPool) -> ServiceResult<Option<(Roles, bool)>> {
+    let query = sqlx::query!("SELECT roles, is_team FROM tokens WHERE owner_id = $1", id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(query.map(|i| (Roles::from_bits_truncate(i.roles as u32), i.is_team)))
+}
+
+/// Errors unless the principal is above the target in the staff hierarchy.
+fn check_rank(principal: &Principal, target: Roles) -> ServiceResult<()> {
+    if principal.roles.rank() > target.rank() {
+        Ok(())
+    } else {
+        Err(ServiceError::Forbidden(
+            "You can only manage accounts below your own rank.".into(),
+        ))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoleData {
+    id: i64,
+    role: String,
+    grant: bool,
+}
+
+/// Grants or revokes a role of a user.
+///
+/// Owners and Admins can only manage users below their own rank, and only grant the roles from
+/// `Roles::grantable`.
+pub async fn set_role(
+    principal: Principal,
+    data: web::Form<RoleData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let admin_id = get_admin(&principal)?;
+
+    let role = match data.role.parse::<Roles>() {
+        Ok(x) => x,
+        Err(why) => return Ok(HttpResponse::BadRequest().body(why)),
+    };
+
+    let roles = match get_account(data.id, pool).await? {
+        Some((roles, false)) => roles,
+        _ => return Ok(HttpResponse::BadRequest().body("This user does not exist.")),
+    };
+
+    check_rank(&principal, roles)?;
+
+    if !principal.roles.grantable().contains(role) {
+        return Err(ServiceError::Forbidden(format!(
+            "You can't grant or revoke the `{:?}` role.",
+            role
+        )));
+    }
+
+    let new_roles = if data.grant {
+        roles | role
+    } else {
+        roles - role
+    };
+
+    sqlx::query!(
+        "UPDATE tokens SET roles = $2 WHERE owner_id = $1",
+        data.id,
+        new_roles.bits() as i32,
+    )
+    .execute(pool)
+    .await?;
+
+    AuditEntry {
+        action: if data.grant {
+            "grant_role"
+        } else {
+            "revoke_role"
+        },
+        target_id: Some(data.id),
+        details: Some(format!("{:?}", role)),
+        ..Default::default()
+    }
+    .log(admin_id, pool)
+    .await?;
+
+    if data.grant {
+        Ok(HttpResponse::Ok().body(format!("Granted `{:?}` to {}.", role, data.id)))
+    } else {
+        Ok(HttpResponse::Ok().body(format!("Revoked `{:?}` from {}.", role, data.id)))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BanData {
+    id: i64,
+    reason: String,
+    /// Days until the ban is lifted, it's permanent when missing.
+    days: Option<i32>,
+}
+
+/// Bans a user or a team, rejecting every request made with their cookies or tokens.
+pub async fn ban(
+    principal: Principal,
+    data: web::Form<BanData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let moderator_id = get_moderator(&principal)?;
+
+    if data.reason.trim().is_empty() {
+        return Ok(HttpResponse::BadRequest().body("A reason is required."));
+    }
+
+    if let Some(days) = data.days {
+        if !(1..=3650).contains(&days) {
+            return Ok(HttpResponse::BadRequest().body("Bans can last between 1 and 3650 days."));
+        }
+    }
+
+    let roles = if let Some((roles, _)) = get_account(data.id, pool).await? {
+        roles
+    } else {
+        // Teams only get an account once they generate a token.
+        let team = sqlx::query!("SELECT id FROM teams WHERE id::BIGINT = $1", data.id)
+            .fetch_optional(pool)
+            .await?;
+
+        if team.is_none() {
+            return Ok(HttpResponse::BadRequest().body("This account does not exist."));
+        }
+
+        create_team_account(data.id, pool).await?;
+
+        Roles::default()
+    };
+
+    check_rank(&principal, roles)?;
+
+    sqlx::query!(
+        "UPDATE tokens SET is_banned = true, ban_reason = $2, banned_by = $3, banned_at = CURRENT_TIMESTAMP, ban_expires = CURRENT_TIMESTAMP + make_interval(days => $4) WHERE owner_id = $1",
+        data.id,
+        data.reason.trim(),
+        moderator_id,
+        data.days,
+    )
+    .execute(pool)
+    .await?;
+
+    let details = match data.days {
+        Some(days) => format!("{} days: {}", days, data.reason.trim()),
+        None => format!("Permanent: {}", data.reason.trim()),
+    };
+
+    AuditEntry {
+        action: "ban",
+        target_id: Some(data.id),
+        details: Some(details),
+        ..Default::default()
+    }
+    .log(moderator_id, pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().body(format!("Successfully banned {}.", data.id)))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnbanData {
+    id: i64,
+}
+
+pub async fn unban(
+    principal: Principal,
+    data: web::Form<UnbanData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let moderator_id = get_moderator(&principal)?;
+
+    if let Some((roles, _)) = get_account(data.id, pool).await? {
+        check_rank(&principal, roles)?;
+    }
+
+    let query = sqlx::query!(
+        "UPDATE tokens SET is_banned = false, ban_reason = NULL, banned_by = NULL, banned_at = NULL, ban_expires = NULL WHERE owner_id = $1 AND is_banned RETURNING owner_id",
+        data.id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.is_some() {
+        AuditEntry {
+            action: "unban",
+            target_id: Some(data.id),
+            ..Default::default()
+        }
+        .log(moderator_id, pool)
+        .await?;
+
+        Ok(HttpResponse::Ok().body(format!("Successfully unbanned {}.", data.id)))
+    } else {
+        Ok(HttpResponse::BadRequest().body("This account is not banned."))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BannedAccount {
+    id: i64,
+    is_team: bool,
+    reason: Option<String>,
+    banned_by: Option<i64>,
+    banned_at: Option<String>,
+    expires: Option<String>,
+}
+
+/// Lists the accounts that are currently banned.
+pub async fn banned(principal: Principal, db: web::Data<PgPool>) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    get_moderator(&principal)?;
+
+    let accounts = sqlx::query!(
+        "SELECT owner_id, is_team, ban_reason, banned_by, banned_at, ban_expires FROM tokens WHERE is_banned AND (ban_expires IS NULL OR ban_expires > CURRENT_TIMESTAMP) ORDER BY banned_at DESC NULLS LAST",
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| BannedAccount {
+        id: i.owner_id,
+        is_team: i.is_team,
+        reason: i.ban_reason,
+        banned_by: i.banned_by,
+        banned_at: i.banned_at.map(|x| x.to_rfc3339()),
+        expires: i.ban_expires.map(|x| x.to_rfc3339()),
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(accounts))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditQueryData {
+    actor: Option<i64>,
+    #[serde(rename = "mod")]
+    mod_name: Option<String>,
+    team: Option<i32>,
+    action: Option<String>,
+    /// RFC 3339 timestamps.
+    since: Option<String>,
+    until: Option<String>,
+    /// Only entries older than this id, for getting the next page.
+    before: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AuditLogEntry {
+    id: i64,
+    actor_id: i64,
+    action: String,
+    #[serde(rename = "mod")]
+    mod_name: Option<String>,
+    checksum: Option<String>,
+    team_id: Option<i32>,
+    target_id: Option<i64>,
+    details: Option<String>,
+    created: String,
+}
+
+/// Searches the audit log, newest entries first.
+///
+/// Every filter is optional, at most 1000 entries are returned at once.
+pub async fn audit_log(
+    principal: Principal,
+    data: web::Query<AuditQueryData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    get_admin(&principal)?;
+
+    let limit = data.limit.unwrap_or(100).clamp(1, 1000);
+
+    let parse_time = |time: &Option<String>| -> ServiceResult<Option<DateTime<Utc>>> {
+        time.as_ref()
+            .map(|i| {
+                DateTime::parse_from_rfc3339(i)
+                    .map(|x| x.with_timezone(&Utc))
+                    .map_err(|_| {
+                        ServiceError::BadRequest(format!("`{}` is not an RFC 3339 timestamp.", i))
+                    })
+            })
+            .transpose()
+    };
+
+    let since = parse_time(&data.since)?;
+    let until = parse_time(&data.until)?;
+
+    let entries = sqlx::query!(
+        r#"
+            SELECT
+                id, actor_id, action, mod_name, checksum, team_id, target_id, details, created
+            FROM
+                audit_log
+            WHERE
+                ($1::BIGINT IS NULL OR actor_id = $1)
+                AND ($2::TEXT IS NULL OR mod_name = $2)
+                AND ($3::INT IS NULL OR team_id = $3)
+                AND ($4::TEXT IS NULL OR action = $4)
+                AND ($5::TIMESTAMPTZ IS NULL OR created >= $5)
+                AND ($6::TIMESTAMPTZ IS NULL OR created < $6)
+                AND ($7::BIGINT IS NULL OR id < $7)
+            ORDER BY id DESC
+            LIMIT $8
+        "#,
+        data.actor,
+        data.mod_name.as_ref(),
+        data.team,
+        data.action.as_ref(),
+        since,
+        until,
+        data.before,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| AuditLogEntry {
+        id: i.id,
+        actor_id: i.actor_id,
+        action: i.action,
+        mod_name: i.mod_name,
+        checksum: i.checksum,
+        team_id: i.team_id,
+        target_id: i.target_id,
+        details: i.details,
+        created: i.created.to_rfc3339(),
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CoreData {
+    #[serde(rename = "mod")]
+    mod_name: String,
+    core: bool,
+    reason: Option<String>,
+}
+
+/// Promotes every version of a mod to `Core`, or demotes it back to the level it had before, with
+/// the votes cast since applied.
+pub async fn core(
+    principal: Principal,
+    data: web::Form<CoreData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let admin_id = get_admin(&principal)?;
+
+    let mut transaction = pool.begin().await?;
+
+    // The checksums of the versions changed, with the level they had before.
+    let query = if data.core {
+        sqlx::query!(
+            r#"
+                WITH previous AS (
+                    SELECT checksum, verification FROM mods
+                    WHERE name = $1 AND verification IS DISTINCT FROM 'Core'
+                    FOR UPDATE
+                )
+                UPDATE mods m SET verification = 'Core'
+                FROM previous p
+                WHERE m.checksum = p.checksum
+                RETURNING m.checksum, p.verification as "verification: Verification"
+            "#,
+            &data.mod_name,
+        )
+        .fetch_all(&mut transaction)
+        .await?
+        .into_iter()
+        .map(|i| (i.checksum, i.verification.unwrap_or_default()))
+        .collect::<Vec<_>>()
+    } else {
+        // Versions go back to the level they had when they were promoted, the votes cast since
+        // are applied by `update_verification`.
+        sqlx::query!(
+            r#"
+                UPDATE mods m SET verification = (
+                    SELECT o.previous_verification FROM verification_overrides o
+                    WHERE o.checksum = m.checksum AND o.verification = 'Core'
+                    ORDER BY o.id DESC
+                    LIMIT 1
+                )
+                WHERE m.name = $1 AND m.verification = 'Core'
+                RETURNING m.checksum
+            "#,
+            &data.mod_name,
+        )
+        .fetch_all(&mut transaction)
+        .await?
+        .into_iter()
+        .map(|i| (i.checksum, Verification::Core))
+        .collect::<Vec<_>>()
+    };
+
+    if query.is_empty() {
+        return Ok(HttpResponse::BadRequest().body("No versions of this mod were changed."));
+    }
+
+    for (checksum, previous) in &query {
+        let verification = if data.core {
+            Verification::Core
+        } else {
+            update_verification(checksum, &mut transaction).await?
+        };
+
+        let level = |verification: &Verification| {
+            if *verification == Verification::None {
+                None
+            } else {
+                Some(verification.to_string())
+            }
+        };
+
+        sqlx::query!(
+            "INSERT INTO verification_overrides (checksum, admin_id, verification, previous_verification, reason) VALUES ($1, $2, $3::text::verification_level, $4::text::verification_level, $5)",
+            checksum,
+            admin_id,
+            level(&verification),
+            level(previous),
+            data.reason.as_ref(),
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    AuditEntry {
+        action: if data.core {
+            "mark_core"
+        } else {
+            "unmark_core"
+        },
+        mod_name: Some(&data.mod_name),
+        details: data.reason.clone(),
+        ..Default::default()
+    }
+    .log(admin_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    if data.core {
+        Ok(HttpResponse::Ok().body(format!(
+            "Successfully marked {} versions of `{}` as Core.",
+            query.len(),
+            data.mod_name
+        )))
+    } else {
+        Ok(HttpResponse::Ok().body(format!(
+            "Successfully removed Core from {} versions of `{}`.",
+            query.len(),
+            data.mod_name
+        )))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnsafeData {
+    checksum: String,
+    advisory: String,
+}
+
+/// Marks a mod version as `Unsafe` regardless of the votes, and publishes a Critical advisory
+/// for it.
+///
+/// Marking a version again with the same reason changes nothing, and a version only ever gets one
+/// active advisory with the same title. Returns false if the mod version does not exist.
+pub async fn mark_unsafe(
+    checksum: &str,
+    title: &str,
+    reason: &str,
+    admin_id: i64,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> ServiceResult<bool> {
+    let query = sqlx::query!(
+        r#"SELECT name, version, verification as "verification: Verification", unsafe_reason FROM mods WHERE checksum = $1 FOR UPDATE"#,
+        checksum,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    let current = if let Some(x) = query {
+        x
+    } else {
+        return Ok(false);
+    };
+
+    if current.verification == Some(Verification::Unsafe)
+        && current.unsafe_reason.as_deref() == Some(reason)
+    {
+        return Ok(true);
+    }
+
+    sqlx::query!(
+        "UPDATE mods SET verification = 'Unsafe', unsafe_reason = $2 WHERE checksum = $1",
+        checksum,
+        reason,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    let versions = format!("={}", current.version);
+
+    sqlx::query!(
+        r#"
+            INSERT INTO advisories
+                (mod_name, versions, severity, title, description, author_id)
+            SELECT
+                $1, $2, 'Critical', $3, $4, $5
+            WHERE
+                NOT EXISTS (
+                    SELECT 1 FROM advisories
+                    WHERE mod_name = $1 AND versions = $2 AND title = $3 AND NOT withdrawn
+                )
+        "#,
+        &current.name,
+        &versions,
+        title,
+        reason,
+        admin_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO verification_overrides (checksum, admin_id, verification, reason) VALUES ($1, $2, 'Unsafe', $3)",
+        checksum,
+        admin_id,
+        reason,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    AuditEntry {
+        action: "mark_unsafe",
+        mod_name: Some(&current.name),
+        checksum: Some(checksum),
+        details: Some(reason.to_string()),
+        ..Default::default()
+    }
+    .log(admin_id, &mut *transaction)
+    .await?;
+
+    Ok(true)
+}
+
+pub async fn force_unsafe(
+    principal: Principal,
+    data: web::Form<UnsafeData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let admin_id = get_admin(&principal)?;
+
+    if !data.advisory.contains(' ') || data.advisory.len() < 60 {
+        return Ok(HttpResponse::BadRequest().body("Invalid or too short of an advisory."));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    if !mark_unsafe(
+        &data.checksum,
+        "Marked as Unsafe by an admin",
+        &data.advisory,
+        admin_id,
+        &mut transaction,
+    )
+    .await?
+    {
+        return Ok(HttpResponse::BadRequest().body("This mod does not exist."));
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body("Successfully marked mod as Unsafe."))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OverrideData {
+    checksum: String,
+    verification: Verification,
+    reason: String,
+}
+
+/// Sets the verification level of a mod version, for verifications that are stuck.
+///
+/// `Core` and `Unsafe` have their own endpoints, as they affect more than the verification level.
+pub async fn override_verification(
+    principal: Principal,
+    data: web::Form<OverrideData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let admin_id = get_admin(&principal)?;
+
+    match data.verification {
+        Verification::Core | Verification::Unsafe | Verification::Yanked => {
+            return Ok(HttpResponse::BadRequest()
+                .body("Only None, Auto and Manual can be set through an override."))
+        }
+        _ => (),
+    }
+
+    let query = sqlx::query!(
+        r#"SELECT verification as "verification: Verification" FROM mods WHERE checksum = $1"#,
+        &data.checksum,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(x) = query {
+        if x.verification == Some(Verification::Core) {
+            return Ok(HttpResponse::BadRequest().body("Cannot override Core mods."));
+        }
+    } else {
+        return Ok(HttpResponse::BadRequest().body("This mod does not exist."));
+    }
+
+    let value = if data.verification == Verification::None {
+        None
+    } else {
+        Some(data.verification.to_string())
+    };
+
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE mods SET verification = $2::text::verification_level, unsafe_reason = NULL WHERE checksum = $1",
+        &data.checksum,
+        value,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO verification_overrides (checksum, admin_id, verification, reason) VALUES ($1, $2, $3::text::verification_level, $4)",
+        &data.checksum,
+        admin_id,
+        value,
+        &data.reason,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    AuditEntry {
+        action: "override_verification",
+        checksum: Some(&data.checksum),
+        details: Some(format!("{}: {}", data.verification, data.reason)),
+        ..Default::default()
+    }
+    .log(admin_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Successfully set the mod verification to `{}`.",
+        data.verification
+    )))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CoreModResponse {
+    name: String,
+}
+
+/// Lists the names of the mods with at least one Core version, used by the navbar.
+pub async fn core_mods(db: web::Data<PgPool>) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+
+    let mods = sqlx::query_as!(
+        CoreModResponse,
+        "SELECT DISTINCT name FROM mods WHERE verification = 'Core' ORDER BY name"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(mods))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BlocklistEntry {
+    hash: String,
+    reason: String,
+    admin_id: i64,
+    created: String,
+}
+
+pub async fn blocklist(principal: Principal, db: web::Data<PgPool>) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    get_admin(&principal)?;
+
+    let entries = sqlx::query!("SELECT * FROM blocklist ORDER BY created DESC")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|i| BlocklistEntry {
+            hash: i.hash,
+            reason: i.reason,
+            admin_id: i.admin_id,
+            created: i.created.to_rfc3339(),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockData {
+    hash: String,
+    reason: String,
+}
+
+/// Adds a hash to the blocklist, and marks every mod version that matches it as `Unsafe`.
+///
+/// The hash can be the checksum of a mod package, or of any of the files inside one.
+pub async fn block(
+    principal: Principal,
+    data: web::Form<BlockData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let admin_id = get_admin(&principal)?;
+
+    let hash = data.hash.to_lowercase();
+
+    match hex::decode(&hash) {
+        Ok(x) if x.len() != 32 => {
+            return Ok(HttpResponse::BadRequest().body("Invalid hash length"));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::BadRequest().body("Invalid characters found in hash"));
+        }
+        _ => (),
+    }
+
+    if data.reason.is_empty() {
+        return Ok(HttpResponse::BadRequest().body("A reason is required."));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    let query = sqlx::query!(
+        "INSERT INTO blocklist (hash, reason, admin_id) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING RETURNING hash",
+        &hash,
+        &data.reason,
+        admin_id,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+
+    if query.is_none() {
+        return Ok(HttpResponse::BadRequest().body("This hash is already blocklisted."));
+    }
+
+    let matches = sqlx::query!(
+        "SELECT checksum FROM mods WHERE checksum = $1 OR checksum IN (SELECT checksum FROM mod_files WHERE hash = $1)",
+        &hash,
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+
+    for i in &matches {
+        mark_unsafe(
+            &i.checksum,
+            "Contains known malware",
+            &data.reason,
+            admin_id,
+            &mut transaction,
+        )
+        .await?;
+    }
+
+    AuditEntry {
+        action: "block_hash",
+        details: Some(format!("{}: {}", hash, data.reason)),
+        ..Default::default()
+    }
+    .log(admin_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Blocklisted the hash, {} mod versions have been marked as Unsafe.",
+        matches.len()
+    )))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HashFilesData {
+    /// The `last_id` of the previous batch.
+    after: Option<i32>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HashFilesResponse {
+    hashed: usize,
+    /// Checksums of the archives that could not be read.
+    failed: Vec<String>,
+    marked_unsafe: usize,
+    /// `None` once every archive has been checked.
+    last_id: Option<i32>,
+}
+
+/// Hashes the files inside the archives uploaded before their files started being hashed, so the
+/// blocklist can match them, and marks the versions that contain a blocklisted file as `Unsafe`.
+///
+/// Works in batches of up to `limit` versions, call it again with `after` set to the `last_id`
+/// of the previous batch until it is `None`.
+pub async fn hash_old_files(
+    principal: Principal,
+    data: web::Form<HashFilesData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let admin_id = get_admin(&principal)?;
+
+    let versions = sqlx::query!(
+        r#"
+            SELECT
+                id,
+                checksum
+            FROM
+                mods
+            WHERE
+                id > $1
+                AND NOT EXISTS (SELECT 1 FROM mod_files f WHERE f.checksum = mods.checksum)
+            ORDER BY
+                id
+            LIMIT $2
+        "#,
+        data.after.unwrap_or(0),
+        data.limit.unwrap_or(100).clamp(1, 1000),
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut response = HashFilesResponse {
+        hashed: 0,
+        failed: Vec::new(),
+        marked_unsafe: 0,
+        last_id: versions.last().map(|i| i.id),
+    };
+
+    for i in &versions {
+        let path = archive_path(&i.checksum);
+
+        let file_hashes = match web::block(move || hash_files(&path)).await {
+            Ok(x) => x,
+            Err(why) => {
+                warn!("Could not hash the files of `{}`: {}", i.checksum, why);
+                response.failed.push(i.checksum.to_string());
+                continue;
+            }
+        };
+
+        let (filenames, hashes): (Vec<String>, Vec<String>) = file_hashes.into_iter().unzip();
+
+        let mut transaction = pool.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO mod_files (checksum, filename, hash) SELECT $1, * FROM UNNEST($2::text[], $3::text[]) ON CONFLICT DO NOTHING",
+            &i.checksum,
+            &filenames,
+            &hashes,
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        let blocked = sqlx::query!(
+            "SELECT reason FROM blocklist WHERE hash = ANY($1) LIMIT 1",
+            &hashes,
+        )
+        .fetch_optional(&mut transaction)
+        .await?;
+
+        if let Some(blocked) = blocked {
+            mark_unsafe(
+                &i.checksum,
+                "Contains known malware",
+                &blocked.reason,
+                admin_id,
+                &mut transaction,
+            )
+            .await?;
+
+            response.marked_unsafe += 1;
+        }
+
+        transaction.commit().await?;
+
+        response.hashed += 1;
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnblockData {
+    hash: String,
+}
+
+/// Removes a hash from the blocklist.
+///
+/// Mod versions marked as `Unsafe` when it was added are left as they are.
+pub async fn unblock(
+    principal: Principal,
+    data: web::Form<UnblockData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let admin_id = get_admin(&principal)?;
+
+    let query = sqlx::query!(
+        "DELETE FROM blocklist WHERE hash = $1 RETURNING hash",
+        data.hash.to_lowercase(),
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.is_some() {
+        AuditEntry {
+            action: "unblock_hash",
+            details: Some(data.hash.to_lowercase()),
+            ..Default::default()
+        }
+        .log(admin_id, pool)
+        .await?;
+
+        Ok(HttpResponse::Ok().body("Removed the hash from the blocklist."))
+    } else {
+        Ok(HttpResponse::BadRequest().body("This hash is not blocklisted."))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReservedNameEntry {
+    name: String,
+    reserved_by: Option<String>,
+    created: String,
+}
+
+/// Lists the mod names only Admins and Owners can register.
+pub async fn reserved_names(
+    principal: Principal,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    get_admin(&principal)?;
+
+    let entries = sqlx::query!("SELECT * FROM reserved_mod_names ORDER BY normalized")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|i| ReservedNameEntry {
+            name: i.name,
+            reserved_by: i.reserved_by.map(|i| i.to_string()),
+            created: i.created.to_rfc3339(),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReserveData {
+    name: String,
+}
+
+/// Reserves a mod name, and the ones that look like it, for Admins and Owners.
+///
+/// Mods that already have a similar name keep it.
+pub async fn reserve(
+    principal: Principal,
+    data: web::Form<ReserveData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let admin_id = get_admin(&principal)?;
+
+    let name = data.name.trim();
+    let normalized = normalize(name);
+
+    if normalized.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "Reserved names must be between 1 and {} characters.",
+            MAX_NAME_LENGTH
+        )));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    let query = sqlx::query!(
+        "INSERT INTO reserved_mod_names (normalized, name, skeleton, reserved_by) VALUES ($1, $2, mod_name_skeleton($2), $3) ON CONFLICT DO NOTHING RETURNING normalized",
+        &normalized,
+        name,
+        admin_id,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+
+    if query.is_none() {
+        return Ok(HttpResponse::BadRequest().body("This name is already reserved."));
+    }
+
+    AuditEntry {
+        action: "reserve_name",
+        details: Some(name.to_string()),
+        ..Default::default()
+    }
+    .log(admin_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body("Successfully reserved the name."))
+}
+
+/// Lets anyone register a reserved name again.
+pub async fn unreserve(
+    principal: Principal,
+    data: web::Form<ReserveData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let admin_id = get_admin(&principal)?;
+
+    let query = sqlx::query!(
+        "DELETE FROM reserved_mod_names WHERE normalized = $1 RETURNING name",
+        normalize(&data.name),
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let query = if let Some(x) = query {
+        x
+    } else {
+        return Ok(HttpResponse::BadRequest().body("This name is not reserved."));
+    };
+
+    AuditEntry {
+        action: "unreserve_name",
+        details: Some(query.name),
+        ..Default::default()
+    }
+    .log(admin_id, pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().body("Successfully removed the reservation."))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReclaimData {
+    #[serde(rename = "mod")]
+    mod_name: String,
+    /// The user or team that gets the mod.
+    to: i64,
+    reason: String,
+}
+
+/// Gives an abandoned mod to someone else, replacing all of its owners.
+///
+/// A mod is abandoned when none of its versions were uploaded in the last
+/// [`ABANDONED_DAYS`] days. Its versions stay available, the new owner can only publish newer
+/// ones. Nothing set by the previous owners carries over, including its deprecation.
+pub async fn reclaim(
+    principal: Principal,
+    data: web::Form<ReclaimData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let admin_id = get_admin(&principal)?;
+
+    if data.reason.is_empty() {
+        return Ok(HttpResponse::BadRequest().body("A reason is required."));
+    }
+
+    let is_team = match get_account(data.to, pool).await? {
+        Some((_, is_team)) => is_team,
+        None => return Ok(HttpResponse::BadRequest().body("This account does not exist.")),
+    };
+
+    let query = sqlx::query!(
+        r#"
+            SELECT
+                MAX(uploaded) < CURRENT_TIMESTAMP - make_interval(days => $2) as abandoned
+            FROM
+                mods
+            WHERE
+                name = $1
+        "#,
+        &data.mod_name,
+        ABANDONED_DAYS,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    match query.abandoned {
+        Some(true) => (),
+        Some(false) => {
+            return Ok(HttpResponse::BadRequest().body(format!(
+                "Only mods without a new version in the last {} days can be reclaimed.",
+                ABANDONED_DAYS
+            )));
+        }
+        None => return Ok(HttpResponse::BadRequest().body("This mod does not exist.")),
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    let previous = sqlx::query!(
+        "DELETE FROM owners WHERE mod_name = $1 RETURNING owner_id",
+        &data.mod_name,
+    )
+    .fetch_all(&mut transaction)
+    .await?
+    .into_iter()
+    .map(|i| i.owner_id.to_string())
+    .collect::<Vec<_>>();
+
+    sqlx::query!(
+        "INSERT INTO owners (owner_id, mod_name, checksums, is_team) VALUES ($1, $2, ARRAY(SELECT checksum FROM mods WHERE name = $2), $3)",
+        data.to,
+        &data.mod_name,
+        is_team,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    // Nothing the previous owners set up for the mod carries over.
+    sqlx::query!(
+        "DELETE FROM ownership_invites WHERE mod_name = $1",
+        &data.mod_name,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM team_mod_permissions WHERE mod_name = $1",
+        &data.mod_name,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM mod_metadata WHERE mod_name = $1",
+        &data.mod_name,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM mod_deprecations WHERE mod_name = $1",
+        &data.mod_name,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    AuditEntry {
+        action: "reclaim_name",
+        mod_name: Some(&data.mod_name),
+        target_id: Some(data.to),
+        details: Some(format!(
+            "{} (previous owners: {})",
+            data.reason,
+            previous.join(", ")
+        )),
+        ..Default::default()
+    }
+    .log(admin_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body("Successfully reclaimed the mod."))
+}
@@ -0,0 +1,334 @@
+use crate::auth::Principal;
+use crate::error::*;
+use crate::model::*;
+use crate::utils::audit::AuditEntry;
+use actix_web::{web, HttpResponse};
+use semver::{Version, VersionReq};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct AdvisoryQueryData {
+    pub id: i32,
+    pub mod_name: String,
+    pub versions: String,
+    pub severity: Severity,
+    pub title: String,
+    pub description: String,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Advisory {
+    pub id: i32,
+    #[serde(rename = "mod")]
+    pub mod_name: String,
+    pub versions: String,
+    pub severity: Severity,
+    pub title: String,
+    pub description: String,
+    pub created: String,
+}
+
+impl From<AdvisoryQueryData> for Advisory {
+    fn from(data: AdvisoryQueryData) -> Advisory {
+        Advisory {
+            id: data.id,
+            mod_name: data.mod_name,
+            versions: data.versions,
+            severity: data.severity,
+            title: data.title,
+            description: data.description,
+            created: data.created.to_rfc3339(),
+        }
+    }
+}
+
+impl Advisory {
+    /// Checks if the version provided is in the range of versions affected by the advisory.
+    pub fn affects(&self, version: &str) -> bool {
+        match (VersionReq::parse(&self.versions), Version::parse(version)) {
+            (Ok(req), Ok(version)) => req.matches(&version),
+            _ => false,
+        }
+    }
+}
+
+/// Gets the active advisories of a mod, for every version.
+pub async fn get_mod_advisories(name: &str, pool: &PgPool) -> ServiceResult<Vec<Advisory>> {
+    let query = sqlx::query_as!(
+        AdvisoryQueryData,
+        r#"
+            SELECT
+                id,
+                mod_name,
+                versions,
+                severity as "severity: Severity",
+                title,
+                description,
+                created
+            FROM
+                advisories
+            WHERE
+                mod_name = $1
+                AND NOT withdrawn
+            ORDER BY
+                created
+                DESC
+        "#,
+        name,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(query.into_iter().map(Advisory::from).collect())
+}
+
+/// Gets the active advisories of the mods with these names, grouped by the mod name.
+pub async fn get_advisories(
+    names: &[String],
+    pool: &PgPool,
+) -> ServiceResult<HashMap<String, Vec<Advisory>>> {
+    let query = sqlx::query_as!(
+        AdvisoryQueryData,
+        r#"
+            SELECT
+                id,
+                mod_name,
+                versions,
+                severity as "severity: Severity",
+                title,
+                description,
+                created
+            FROM
+                advisories
+            WHERE
+                mod_name = ANY($1)
+                AND NOT withdrawn
+            ORDER BY
+                created
+                DESC
+        "#,
+        names,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut advisories: HashMap<String, Vec<Advisory>> = HashMap::new();
+
+    for i in query {
+        advisories
+            .entry(i.mod_name.to_string())
+            .or_default()
+            .push(i.into());
+    }
+
+    Ok(advisories)
+}
+
+/// Gets the active advisories that affect a specific version of a mod.
+pub async fn get_version_advisories(
+    name: &str,
+    version: &str,
+    pool: &PgPool,
+) -> ServiceResult<Vec<Advisory>> {
+    Ok(get_mod_advisories(name, pool)
+        .await?
+        .into_iter()
+        .filter(|i| i.affects(version))
+        .collect())
+}
+
+/// Gets the id of the principal, if they are allowed to publish advisories.
+///
+/// Tokens need either the `verify` or the `admin` scope.
+fn get_publisher(principal: &Principal) -> ServiceResult<i64> {
+    if !principal.scopes.contains(&Scope::Admin) {
+        principal.require_scope(Scope::Verify)?;
+    }
+
+    if principal
+        .roles
+        .intersects(Roles::OWNER | Roles::ADMIN | Roles::VERIFIER)
+    {
+        Ok(principal.id)
+    } else {
+        Err(ServiceError::Unauthorized)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateAdvisoryData {
+    #[serde(rename = "mod")]
+    mod_name: String,
+    versions: String,
+    severity: Severity,
+    title: String,
+    description: String,
+}
+
+pub async fn create(
+    principal: Principal,
+    data: web::Form<CreateAdvisoryData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let author_id = get_publisher(&principal)?;
+
+    if let Err(why) = VersionReq::parse(&data.versions) {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "The versions are not a valid semver requirement: {}",
+            why
+        )));
+    }
+
+    if data.title.is_empty() || data.title.len() > 128 {
+        return Ok(
+            HttpResponse::BadRequest().body("The title must be between 1 and 128 characters.")
+        );
+    }
+
+    if !data.description.contains(' ') || data.description.len() < 60 {
+        return Ok(HttpResponse::BadRequest().body("Invalid or too short of a description."));
+    }
+
+    let query = sqlx::query!(
+        "SELECT id FROM mods WHERE name = $1 LIMIT 1",
+        &data.mod_name
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.is_none() {
+        return Ok(HttpResponse::BadRequest().body("This mod does not exist."));
+    }
+
+    let query = sqlx::query!(
+        "INSERT INTO advisories (mod_name, versions, severity, title, description, author_id) VALUES ($1, $2, $3::text::advisory_severity, $4, $5, $6) RETURNING id",
+        &data.mod_name,
+        &data.versions,
+        data.severity.to_string(),
+        &data.title,
+        &data.description,
+        author_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    AuditEntry {
+        action: "create_advisory",
+        mod_name: Some(&data.mod_name),
+        details: Some(format!("#{}: {}", query.id, data.title)),
+        ..Default::default()
+    }
+    .log(author_id, pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().body(format!("Published the advisory with the id `{}`", query.id)))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WithdrawAdvisoryData {
+    id: i32,
+}
+
+pub async fn withdraw(
+    principal: Principal,
+    data: web::Query<WithdrawAdvisoryData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let author_id = get_publisher(&principal)?;
+
+    // Verifiers can only withdraw their own advisories, Admins and Owners can withdraw any.
+    let query = sqlx::query!(
+        "UPDATE advisories SET withdrawn = true WHERE id = $1 AND NOT withdrawn AND (author_id = $2 OR $3) RETURNING mod_name, title",
+        data.id,
+        author_id,
+        principal.roles.intersects(Roles::OWNER | Roles::ADMIN),
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(advisory) = query {
+        AuditEntry {
+            action: "withdraw_advisory",
+            mod_name: Some(&advisory.mod_name),
+            details: Some(format!("#{}: {}", data.id, advisory.title)),
+            ..Default::default()
+        }
+        .log(author_id, pool)
+        .await?;
+
+        Ok(HttpResponse::Ok().body("Successfully withdrew the advisory."))
+    } else {
+        Ok(HttpResponse::BadRequest().body(
+            "This advisory does not exist, was already withdrawn or was published by someone else.",
+        ))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListAdvisoriesData {
+    #[serde(rename = "mod")]
+    mod_name: String,
+}
+
+pub async fn list(
+    data: web::Query<ListAdvisoriesData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let advisories = get_mod_advisories(&data.mod_name, db.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(advisories))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckAdvisoriesData {
+    checksums: Vec<String>,
+}
+
+/// Finds the advisories affecting each of the checksums provided.
+///
+/// Meant for mod managers, which can send the checksums of every mod they have installed.
+/// Only checksums with at least one advisory are included in the response.
+pub async fn check(
+    data: web::Json<CheckAdvisoriesData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+
+    if data.checksums.len() > 1024 {
+        return Ok(
+            HttpResponse::BadRequest().body("Cannot check more than 1024 checksums at once.")
+        );
+    }
+
+    let mods = sqlx::query!(
+        "SELECT checksum, name, version FROM mods WHERE checksum = ANY($1)",
+        &data.checksums,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let names = mods.iter().map(|i| i.name.to_string()).collect::<Vec<_>>();
+    let advisories = get_advisories(&names, pool).await?;
+    let mut affected = HashMap::new();
+
+    for i in mods {
+        if let Some(mod_advisories) = advisories.get(&i.name) {
+            let version_advisories = mod_advisories
+                .iter()
+                .filter(|a| a.affects(&i.version))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if !version_advisories.is_empty() {
+                affected.insert(i.checksum, version_advisories);
+            }
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(affected))
+}
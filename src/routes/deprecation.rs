@@ -0,0 +1,251 @@
+use crate::auth::Principal;
+use crate::error::*;
+use crate::model::*;
+use crate::utils::audit::AuditEntry;
+use crate::utils::names::normalize;
+use crate::utils::ownership::mod_permissions;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Deprecation {
+    pub message: String,
+    /// The mod to use instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub successor: Option<String>,
+    pub deprecated: String,
+}
+
+impl Deprecation {
+    /// The warning shown when the mod is pulled in as a dependency.
+    pub fn warning(&self, mod_name: &str) -> String {
+        match &self.successor {
+            Some(successor) => format!(
+                "`{}` is deprecated in favor of `{}`: {}",
+                mod_name, successor, self.message
+            ),
+            None => format!("`{}` is deprecated: {}", mod_name, self.message),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeprecatedDependency {
+    pub name: String,
+    #[serde(flatten)]
+    pub deprecation: Deprecation,
+}
+
+/// Gets the deprecation of a mod, `None` if it isn't deprecated.
+pub async fn get_deprecation(name: &str, pool: &PgPool) -> ServiceResult<Option<Deprecation>> {
+    let query = sqlx::query!(
+        "SELECT message, successor, created FROM mod_deprecations WHERE mod_name = $1",
+        name,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(query.map(|i| Deprecation {
+        message: i.message,
+        successor: i.successor,
+        deprecated: i.created.to_rfc3339(),
+    }))
+}
+
+/// Gets the deprecations of the mods with these names that are deprecated, by the mod name.
+pub async fn get_deprecations(
+    names: &[String],
+    pool: &PgPool,
+) -> ServiceResult<HashMap<String, Deprecation>> {
+    let query = sqlx::query!(
+        "SELECT mod_name, message, successor, created FROM mod_deprecations WHERE mod_name = ANY($1)",
+        names,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(query
+        .into_iter()
+        .map(|i| {
+            (
+                i.mod_name,
+                Deprecation {
+                    message: i.message,
+                    successor: i.successor,
+                    deprecated: i.created.to_rfc3339(),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Gets the deprecated mods among the versions with these checksums.
+pub async fn get_deprecated_dependencies(
+    checksums: &[String],
+    pool: &PgPool,
+) -> ServiceResult<Vec<DeprecatedDependency>> {
+    let query = sqlx::query!(
+        r#"
+            SELECT DISTINCT
+                d.mod_name,
+                d.message,
+                d.successor,
+                d.created
+            FROM
+                mods m
+                JOIN mod_deprecations d ON d.mod_name = m.name
+            WHERE
+                m.checksum = ANY($1)
+            ORDER BY
+                d.mod_name
+        "#,
+        checksums,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(query
+        .into_iter()
+        .map(|i| DeprecatedDependency {
+            name: i.mod_name,
+            deprecation: Deprecation {
+                message: i.message,
+                successor: i.successor,
+                deprecated: i.created.to_rfc3339(),
+            },
+        })
+        .collect())
+}
+
+/// Errors unless the principal can manage the mod.
+async fn check_can_manage(
+    principal: &Principal,
+    mod_name: &str,
+    pool: &PgPool,
+) -> ServiceResult<()> {
+    if !principal.can_publish(mod_name) {
+        return Err(ServiceError::Forbidden(format!(
+            "This token is missing the `publish` or `publish:{}` scope.",
+            mod_name
+        )));
+    }
+
+    match mod_permissions(principal.id, mod_name, pool).await? {
+        Some(permissions) if permissions.contains(ModPermissions::MANAGE) => Ok(()),
+        Some(_) => Err(ServiceError::Unauthorized),
+        None => Err(ServiceError::BadRequest("This mod does not exist.".into())),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeprecateData {
+    #[serde(rename = "mod")]
+    mod_name: String,
+    message: String,
+    successor: Option<String>,
+}
+
+/// Marks a whole mod as deprecated, or changes the message and successor of a deprecated mod.
+///
+/// Its versions can still be downloaded, but mods that depend on it get a warning.
+pub async fn deprecate(
+    principal: Principal,
+    data: web::Form<DeprecateData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    check_can_manage(&principal, &data.mod_name, pool).await?;
+
+    let message = data.message.trim();
+
+    if message.is_empty() || message.chars().count() > 512 {
+        return Ok(
+            HttpResponse::BadRequest().body("The message must be between 1 and 512 characters.")
+        );
+    }
+
+    let successor = data
+        .successor
+        .as_deref()
+        .map(str::trim)
+        .filter(|i| !i.is_empty());
+
+    if let Some(successor) = successor {
+        if normalize(successor) == normalize(&data.mod_name) {
+            return Ok(HttpResponse::BadRequest().body("A mod can't be its own successor."));
+        }
+
+        let query = sqlx::query!("SELECT name FROM mods WHERE name = $1 LIMIT 1", successor)
+            .fetch_optional(pool)
+            .await?;
+
+        if query.is_none() {
+            return Ok(HttpResponse::BadRequest().body("The successor does not exist."));
+        }
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO mod_deprecations (mod_name, message, successor, deprecated_by) VALUES ($1, $2, $3, $4) ON CONFLICT (mod_name) DO UPDATE SET message = $2, successor = $3, deprecated_by = $4",
+        &data.mod_name,
+        message,
+        successor,
+        principal.id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    AuditEntry {
+        action: "deprecate",
+        mod_name: Some(&data.mod_name),
+        details: Some(match successor {
+            Some(successor) => format!("{} (successor: {})", message, successor),
+            None => message.to_string(),
+        }),
+        ..Default::default()
+    }
+    .log(principal.id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body("Successfully deprecated the mod."))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UndeprecateData {
+    #[serde(rename = "mod")]
+    mod_name: String,
+}
+
+pub async fn undeprecate(
+    principal: Principal,
+    data: web::Form<UndeprecateData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    check_can_manage(&principal, &data.mod_name, pool).await?;
+
+    let query = sqlx::query!(
+        "DELETE FROM mod_deprecations WHERE mod_name = $1 RETURNING mod_name",
+        &data.mod_name,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.is_none() {
+        return Ok(HttpResponse::BadRequest().body("This mod is not deprecated."));
+    }
+
+    AuditEntry {
+        action: "undeprecate",
+        mod_name: Some(&data.mod_name),
+        ..Default::default()
+    }
+    .log(principal.id, pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().body("Successfully removed the deprecation of the mod."))
+}
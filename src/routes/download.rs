@@ -19,6 +19,20 @@ pub async fn download(
         _ => (),
     }
 
+    let blocked = sqlx::query!(
+        "SELECT reason FROM blocklist WHERE hash = $1 OR hash IN (SELECT hash FROM mod_files WHERE checksum = $1) LIMIT 1",
+        &*checksum
+    )
+    .fetch_optional(&**db)
+    .await?;
+
+    if let Some(blocked) = blocked {
+        return Err(ServiceError::BadRequest(format!(
+            "This mod has been blocklisted: {}",
+            blocked.reason
+        )));
+    }
+
     sqlx::query!(
         "UPDATE mods SET downloads = downloads + 1 WHERE checksum = $1",
         &*checksum
@@ -1,5 +1,9 @@
 use crate::error::{ServiceError, ServiceResult};
 use crate::model::Verification;
+use crate::routes::advisories::{get_version_advisories, Advisory};
+use crate::routes::deprecation::{
+    get_deprecated_dependencies, get_deprecation, DeprecatedDependency, Deprecation,
+};
 use actix_web::{web, HttpResponse};
 use futures::StreamExt;
 use handlebars::Handlebars;
@@ -13,6 +17,9 @@ pub struct QueryModInfo {
     version: Option<String>,
     #[serde(default)]
     verification: Verification,
+    /// Yanked versions are skipped unless this is set.
+    #[serde(default)]
+    include_yanked: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -31,6 +38,22 @@ pub struct GetModsResponse {
     files: Vec<String>,
     downloads: usize,
     uploaded: String,
+    /// The user or team that uploaded the version, as a string as ids don't fit in a JS number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uploaded_by: Option<String>,
+    yanked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    yank_reason: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    advisories: Vec<Advisory>,
+    /// Set when the whole mod is deprecated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deprecated: Option<Deprecation>,
+    /// The dependencies of the version that are deprecated.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    deprecated_dependencies: Vec<DeprecatedDependency>,
+    #[serde(skip)]
+    dependencies_checksums: Vec<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     repository_git: Option<String>,
@@ -70,26 +93,30 @@ pub async fn get_mods_data(data: &QueryModInfo, pool: &PgPool) -> ServiceResult<
             checksum,
             name,
             version,
-            description,
-            repository_git,
-            repository_hg,
+            COALESCE(md.description, mods.description) as "description!",
+            COALESCE(md.repository_git, mods.repository_git) as repository_git,
+            COALESCE(md.repository_hg, mods.repository_hg) as repository_hg,
             authors,
-            documentation,
+            NULLIF(COALESCE(md.documentation, mods.documentation), '') as documentation,
             readme,
             readme_filename,
             license,
             license_filename,
-            homepage,
-            keywords,
+            NULLIF(COALESCE(md.homepage, mods.homepage), '') as homepage,
+            COALESCE(md.keywords, mods.keywords) as keywords,
             build_script,
             native_lib_checksums,
             dependencies_checksums,
             metadata,
             verification as "verification: Verification",
             downloads,
-            uploaded
+            uploaded,
+            uploaded_by,
+            yanked,
+            yank_reason
         FROM
             mods
+            LEFT JOIN mod_metadata md ON md.mod_name = mods.name
         WHERE
             name = $1
         "#,
@@ -98,11 +125,27 @@ pub async fn get_mods_data(data: &QueryModInfo, pool: &PgPool) -> ServiceResult<
     .fetch(pool)
     .boxed();
 
+    // A version pinned with `=1.2.3` is returned even if it was yanked, flagged as such, as yanking
+    // only keeps versions from being picked for ranges and as the latest one.
+    let pinned = data
+        .version
+        .as_deref()
+        .map(str::trim)
+        .filter(|i| i.starts_with('='))
+        .and_then(|i| Version::parse(i[1..].trim()).ok());
+
     while let Some(Ok(values)) = query.next().await {
         if data.verification > values.verification.clone().unwrap_or_default() {
             continue;
         }
 
+        if values.yanked
+            && !data.include_yanked
+            && pinned.as_ref() != Version::parse(&values.version).ok().as_ref()
+        {
+            continue;
+        }
+
         if let Some(ref version) = data.version {
             let v_user = match VersionReq::parse(&version) {
                 Ok(x) => x,
@@ -138,6 +181,13 @@ pub async fn get_mods_data(data: &QueryModInfo, pool: &PgPool) -> ServiceResult<
                         files,
                         downloads: values.downloads as usize,
                         uploaded: values.uploaded.to_rfc3339(),
+                        uploaded_by: values.uploaded_by.map(|i| i.to_string()),
+                        yanked: values.yanked,
+                        yank_reason: values.yank_reason,
+                        advisories: Vec::new(),
+                        deprecated: None,
+                        deprecated_dependencies: Vec::new(),
+                        dependencies_checksums,
 
                         repository_git: values.repository_git,
                         repository_hg: values.repository_hg,
@@ -179,6 +229,13 @@ pub async fn get_mods_data(data: &QueryModInfo, pool: &PgPool) -> ServiceResult<
                     files,
                     downloads: values.downloads as usize,
                     uploaded: values.uploaded.to_rfc3339(),
+                    uploaded_by: values.uploaded_by.map(|i| i.to_string()),
+                    yanked: values.yanked,
+                    yank_reason: values.yank_reason,
+                    advisories: Vec::new(),
+                    deprecated: None,
+                    deprecated_dependencies: Vec::new(),
+                    dependencies_checksums,
 
                     repository_git: values.repository_git,
                     repository_hg: values.repository_hg,
@@ -199,7 +256,16 @@ pub async fn get_mods_data(data: &QueryModInfo, pool: &PgPool) -> ServiceResult<
     }
 
     match mods.last_key_value() {
-        Some(x) => Ok(x.1.clone()),
+        Some(x) => {
+            let mut mod_data = x.1.clone();
+            mod_data.advisories =
+                get_version_advisories(&mod_data.name, &mod_data.version, pool).await?;
+            mod_data.deprecated = get_deprecation(&mod_data.name, pool).await?;
+            mod_data.deprecated_dependencies =
+                get_deprecated_dependencies(&mod_data.dependencies_checksums, pool).await?;
+
+            Ok(mod_data)
+        }
         None => Err(ServiceError::NoContent),
     }
 }
@@ -1,11 +1,15 @@
+use crate::auth::Principal;
 use crate::error::*;
 use crate::model::*;
-use crate::routes::users::get_user_data;
-use crate::utils::tokens::gen_token;
+use crate::providers::{ExternalProfile, Provider, Providers};
+use crate::utils::profiles::{store_oauth_tokens, update_profile};
+use crate::utils::sessions::{create_session, delete_session};
+use crate::utils::tokens::gen_secret;
 
 use actix_identity::Identity;
+use actix_web::cookie::{Cookie, SameSite};
 use actix_web::http::header;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
 
 use darkredis::ConnectionPool;
 use handlebars::Handlebars;
@@ -19,171 +23,367 @@ pub struct UserInfoLogin {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct OAuthCode {
     code: String,
+    state: Option<String>,
 }
 
-pub async fn index(
-    id: Identity,
-    hb: web::Data<Handlebars<'_>>,
-    redis: web::Data<ConnectionPool>,
-) -> ServiceResult<HttpResponse> {
-    let mut conn = redis.get().await;
-
-    if let Some(user_id) = id.identity() {
-        if let Ok(Some(token)) = conn.get(&user_id).await {
-            let token = String::from_utf8(token).unwrap();
-            let user = get_user_data(&token).await?;
+/// Cookie with the `state` of the login in progress, so a login can only be finished by the
+/// browser that started it.
+const STATE_COOKIE: &str = "oauth_state";
 
-            let data = serde_json::json!({
-                "name": user.username,
-                "discriminator": user.discriminator,
-            });
+/// A login in progress, stored in Redis under its `state` until the provider redirects back.
+#[derive(Deserialize, Serialize, Debug)]
+struct LoginState {
+    provider: String,
+    /// The logged in user, when they are linking another account to theirs.
+    link_to: Option<i64>,
+}
 
-            let body = hb.render("discord_user", &data).unwrap();
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ProviderInfo {
+    name: String,
+    display_name: String,
+}
 
-            return Ok(HttpResponse::Ok().body(body));
-        }
-    }
+fn get_provider<'a>(providers: &'a Providers, name: &str) -> ServiceResult<&'a Provider> {
+    providers
+        .get(name)
+        .ok_or_else(|| ServiceError::BadRequest(format!("Unknown identity provider `{}`", name)))
+}
 
-    Ok(HttpResponse::Found()
-        .header(header::LOCATION, "/login")
-        .finish())
+/// Lists the providers users can log in with.
+pub async fn providers(providers: web::Data<Providers>) -> HttpResponse {
+    let providers = providers
+        .iter()
+        .map(|i| ProviderInfo {
+            name: i.name.to_string(),
+            display_name: i.display_name.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    HttpResponse::Ok().json(providers)
 }
 
 pub async fn login(
     id: Identity,
     hb: web::Data<Handlebars<'_>>,
-    redis: web::Data<ConnectionPool>,
-    config: web::Data<Config>,
+    providers: web::Data<Providers>,
 ) -> HttpResponse {
-    let mut conn = redis.get().await;
-
-    if let Some(user_id) = id.identity() {
-        if let Ok(Some(_token)) = conn.get(&user_id).await {
-            return HttpResponse::Found().header(header::LOCATION, "/").finish();
-        }
+    if id.identity().is_some() {
+        return HttpResponse::Found().header(header::LOCATION, "/").finish();
     }
 
-    let auth_url = config.oauth2_url.to_string();
+    let providers = providers
+        .iter()
+        .map(|i| ProviderInfo {
+            name: i.name.to_string(),
+            display_name: i.display_name.to_string(),
+        })
+        .collect::<Vec<_>>();
 
     let data = serde_json::json!({
-        "auth_url": auth_url,
+        "providers": providers,
     });
 
-    let body = hb.render("discord_login", &data).unwrap();
+    let body = hb.render("login", &data).unwrap();
 
     HttpResponse::Ok().body(&body)
 }
 
-pub async fn get_token(
-    id: Identity,
+/// Redirects to the login page of a provider.
+///
+/// Logged in users are linking the account to their own instead.
+pub async fn provider_login(
+    name: web::Path<String>,
+    principal: Option<Principal>,
     redis: web::Data<ConnectionPool>,
-    db: web::Data<PgPool>,
+    providers: web::Data<Providers>,
     config: web::Data<Config>,
 ) -> ServiceResult<HttpResponse> {
-    let pool = &**db;
+    let provider = get_provider(&providers, &name)?;
+    let state = gen_secret(32);
+
+    let login = serde_json::to_string(&LoginState {
+        provider: provider.name.to_string(),
+        link_to: principal.filter(|i| i.session_id.is_some()).map(|i| i.id),
+    })?;
+
     let mut conn = redis.get().await;
+    conn.set_and_expire_seconds(format!("oauth_state:{}", state), login, 600)
+        .await?;
 
-    if let Some(user_id) = id.identity() {
-        if let Ok(Some(token)) = conn.get(&user_id).await {
-            let token = String::from_utf8(token).unwrap();
-            let user = get_user_data(&token).await?;
-
-            let query = sqlx::query!(
-                "SELECT token, is_banned FROM tokens WHERE owner_id = $1 AND email = $2",
-                user.id as i64,
-                &user.email
-            )
-            .fetch_optional(pool)
-            .await?;
-
-            if let Some(data) = query {
-                if data.is_banned {
-                    return Ok(HttpResponse::Ok().body("Account has been banned."));
-                } else {
-                    return Ok(HttpResponse::Ok().body(data.token));
-                }
-            } else {
-                let token = gen_token(
-                    user.id,
-                    &user.email,
-                    &hex::decode(&config.secret_key).unwrap(),
-                    &hex::decode(&config.iv_key).unwrap(),
-                )
-                .unwrap_or_else(|| "null".to_string());
-
-                sqlx::query!(
-                    "INSERT INTO tokens (owner_id, email, token) VALUES ($1, $2, $3)",
-                    user.id as i64,
-                    &user.email,
-                    &token
-                )
-                .execute(pool)
-                .await?;
-
-                return Ok(HttpResponse::Ok().body(&format!("User registered, your token is: {}", token)));
-            }
-        }
-    }
+    // Always Lax, as the provider redirecting back is a request from another site.
+    let cookie = Cookie::build(STATE_COOKIE, state.to_string())
+        .path("/")
+        .http_only(true)
+        .secure(config.secure_cookies)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::minutes(10))
+        .finish();
 
-    Ok(HttpResponse::Ok().body("null"))
+    Ok(HttpResponse::Found()
+        .cookie(cookie)
+        .header(header::LOCATION, provider.authorize_url(&state)?)
+        .finish())
 }
 
-pub async fn logout(id: Identity, redis: web::Data<ConnectionPool>) -> HttpResponse {
-    if let Some(user_id) = id.identity() {
-        let mut conn = redis.get().await;
+/// Gets the login started with the `state` provided, it can only be used once.
+async fn take_login_state(
+    req: &HttpRequest,
+    state: Option<&str>,
+    redis: &ConnectionPool,
+) -> ServiceResult<LoginState> {
+    let invalid = || ServiceError::BadRequest("Invalid login state, please try again.".into());
 
-        if let Err(why) = conn.del(user_id).await {
-            error!("Error deleting cookie: {}", why);
-        }
+    let state = state.ok_or_else(invalid)?;
+
+    match req.cookie(STATE_COOKIE) {
+        Some(cookie) if cookie.value() == state => (),
+        _ => return Err(invalid()),
+    }
+
+    let key = format!("oauth_state:{}", state);
+    let mut conn = redis.get().await;
+    let login = conn.get(&key).await?;
+    conn.del(&key).await?;
+
+    login
+        .and_then(|i| serde_json::from_slice(&i).ok())
+        .ok_or_else(invalid)
+}
+
+pub async fn logout(id: Identity, db: web::Data<PgPool>) -> ServiceResult<HttpResponse> {
+    if let Some(session) = id.identity() {
+        delete_session(&session, &db).await?;
     }
 
     id.forget();
-    HttpResponse::Found().header(header::LOCATION, "/").finish()
+    Ok(HttpResponse::Found().header(header::LOCATION, "/").finish())
 }
 
+/// Finds the user an external account is linked to.
+///
+/// The account is linked to `current_user` when it's not linked yet, or to a new user when nobody
+/// is logged in.
+async fn find_or_link_account(
+    provider: &str,
+    profile: &ExternalProfile,
+    current_user: Option<i64>,
+    pool: &PgPool,
+) -> ServiceResult<i64> {
+    let query = sqlx::query!(
+        "SELECT user_id FROM linked_accounts WHERE provider = $1 AND external_id = $2",
+        provider,
+        &profile.id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(data) = query {
+        return match current_user {
+            Some(user_id) if user_id != data.user_id => Err(ServiceError::BadRequest(
+                "This account is already linked to another user.".into(),
+            )),
+            _ => Ok(data.user_id),
+        };
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    let user_id = if let Some(user_id) = current_user {
+        let query = sqlx::query!(
+            "SELECT external_id FROM linked_accounts WHERE user_id = $1 AND provider = $2",
+            user_id,
+            provider,
+        )
+        .fetch_optional(&mut transaction)
+        .await?;
+
+        if query.is_some() {
+            return Err(ServiceError::BadRequest(format!(
+                "Another `{}` account is already linked to this user.",
+                provider
+            )));
+        }
+
+        user_id
+    } else {
+        // Users are only reachable through the providers, the email is kept for contacting them.
+        let email = profile
+            .email
+            .clone()
+            .unwrap_or_else(|| format!("{}@{}", profile.id, provider));
+
+        sqlx::query!(
+            "INSERT INTO tokens (owner_id, email) VALUES (nextval('user_ids'), $1) RETURNING owner_id",
+            email,
+        )
+        .fetch_one(&mut transaction)
+        .await?
+        .owner_id
+    };
+
+    sqlx::query!(
+        "INSERT INTO linked_accounts (provider, external_id, user_id, username, email) VALUES ($1, $2, $3, $4, $5)",
+        provider,
+        &profile.id,
+        user_id,
+        &profile.username,
+        profile.email.as_ref(),
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(user_id)
+}
+
+/// The page providers redirect to after logging in.
 pub async fn oauth(
+    req: HttpRequest,
+    name: web::Path<String>,
     code: web::Query<OAuthCode>,
     id: Identity,
     redis: web::Data<ConnectionPool>,
-    config: web::Data<Config>,
+    db: web::Data<PgPool>,
+    providers: web::Data<Providers>,
 ) -> ServiceResult<HttpResponse> {
-    let code = code.code.to_string();
-    let mut conn = redis.get().await;
+    let pool = &**db;
+    let provider = get_provider(&providers, &name)?;
 
-    let client_id = config.client_id;
-    let client_secret = config.client_secret.to_string();
-    let redirect_uri = config.redirect_uri.to_string();
+    let login = take_login_state(&req, code.state.as_deref(), &redis).await?;
 
-    let data = OAuthTokenData {
-        client_id,
-        client_secret,
-        code,
-        redirect_uri,
-        scope: "identify email guilds".to_string(),
-        grant_type: "authorization_code".to_string(),
+    if login.provider != provider.name {
+        return Ok(HttpResponse::BadRequest().body("Invalid login state, please try again."));
+    }
+
+    let resp = provider.exchange_code(&code.code).await?;
+    let profile = provider.get_profile(&resp.access_token).await?;
+
+    let current_user = login.link_to;
+    let user_id = find_or_link_account(&provider.name, &profile, current_user, pool).await?;
+
+    store_oauth_tokens(&provider.name, &profile.id, &resp, pool).await?;
+
+    // Linking another account keeps the profile and the session of the one the user logged in
+    // with.
+    if current_user.is_none() {
+        update_profile(user_id, &provider.name, &profile, pool).await?;
+
+        let session = create_session(&req, user_id, &provider.name, pool).await?;
+        id.remember(session);
+    }
+
+    let location = if current_user.is_some() {
+        "/profile"
+    } else {
+        "/"
     };
 
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(&format!("{}/oauth2/token", API_ENDPOINT))
-        .form(&data)
-        .send()
-        .await?
-        .json::<OAuthResponse>()
-        .await?;
+    Ok(HttpResponse::Found()
+        .del_cookie(&Cookie::build(STATE_COOKIE, "").path("/").finish())
+        .header(header::LOCATION, location)
+        .finish())
+}
 
-    let user = get_user_data(&resp.access_token).await?;
+/// `/discord/oauth2`, the redirect uri of Discord from before other providers were supported.
+pub async fn discord_oauth(
+    req: HttpRequest,
+    code: web::Query<OAuthCode>,
+    id: Identity,
+    redis: web::Data<ConnectionPool>,
+    db: web::Data<PgPool>,
+    providers: web::Data<Providers>,
+) -> ServiceResult<HttpResponse> {
+    oauth(
+        req,
+        web::Path::from("discord".to_string()),
+        code,
+        id,
+        redis,
+        db,
+        providers,
+    )
+    .await
+}
 
-    id.remember(user.id.to_string());
-    conn.set_and_expire_seconds(&user.id.to_string(), &resp.access_token, resp.expires_in)
-        .await
-        .unwrap();
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LinkedAccount {
+    provider: String,
+    external_id: String,
+    username: Option<String>,
+    created: String,
+}
 
-    Ok(HttpResponse::Found().header(header::LOCATION, "/").finish())
+/// Lists the external accounts linked to the logged in user.
+pub async fn linked_accounts(
+    principal: Principal,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_scope(Scope::Read)?;
+    let user_id = principal.user_id()?;
+
+    let accounts = sqlx::query!(
+        "SELECT provider, external_id, username, created FROM linked_accounts WHERE user_id = $1 ORDER BY created",
+        user_id,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| LinkedAccount {
+        provider: i.provider,
+        external_id: i.external_id,
+        username: i.username,
+        created: i.created.to_rfc3339(),
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(accounts))
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct UnlinkData {
+    provider: String,
 }
 
-pub async fn get_oauth2(config: web::Data<Config>) -> HttpResponse {
-    HttpResponse::Ok().json(serde_json::json!({
-        "url" : config.oauth2_url,
-    }))
+/// Unlinks an external account, unless it's the only way the user can log in.
+pub async fn unlink_account(
+    principal: Principal,
+    data: web::Form<UnlinkData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    let mut transaction = pool.begin().await?;
+
+    let linked = sqlx::query!(
+        "SELECT provider FROM linked_accounts WHERE user_id = $1 FOR UPDATE",
+        user_id,
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+
+    if !linked.iter().any(|i| i.provider == data.provider) {
+        return Ok(HttpResponse::BadRequest().body("No account of this provider is linked."));
+    }
+
+    if linked.len() == 1 {
+        return Ok(HttpResponse::BadRequest()
+            .body("Cannot unlink the last account, you wouldn't be able to log in again."));
+    }
+
+    sqlx::query!(
+        "DELETE FROM linked_accounts WHERE user_id = $1 AND provider = $2",
+        user_id,
+        &data.provider,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body("Successfully unlinked the account."))
 }
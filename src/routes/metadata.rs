@@ -0,0 +1,177 @@
+use crate::auth::Principal;
+use crate::error::*;
+use crate::model::*;
+use crate::utils::audit::AuditEntry;
+use crate::utils::ownership::mod_permissions;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditMetadataData {
+    #[serde(rename = "mod")]
+    mod_name: String,
+    description: Option<String>,
+    /// An http(s) url, empty to remove it.
+    homepage: Option<String>,
+    /// An http(s) url, empty to remove it.
+    documentation: Option<String>,
+    /// Comma separated, empty to remove them.
+    keywords: Option<String>,
+    repository_git: Option<String>,
+    repository_hg: Option<String>,
+}
+
+fn check_url(field: &str, url: &str) -> ServiceResult<()> {
+    if !url.is_empty()
+        && (!(url.starts_with("https://") || url.starts_with("http://")) || url.len() > 512)
+    {
+        return Err(ServiceError::BadRequest(format!(
+            "The {} must be an http(s) url of up to 512 characters.",
+            field
+        )));
+    }
+
+    Ok(())
+}
+
+/// Edits the metadata of every version of a mod, without publishing a new one.
+///
+/// Missing fields are left as they are. An edit is kept until a new version sets the same field,
+/// and only the owners and the members who can manage the mod can edit it.
+pub async fn edit(
+    principal: Principal,
+    data: web::Form<EditMetadataData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+
+    if !principal.can_publish(&data.mod_name) {
+        return Ok(HttpResponse::Forbidden().body(format!(
+            "This token is missing the `publish` or `publish:{}` scope.",
+            data.mod_name
+        )));
+    }
+
+    match mod_permissions(principal.id, &data.mod_name, pool).await? {
+        Some(permissions) if permissions.contains(ModPermissions::MANAGE) => (),
+        Some(_) => return Err(ServiceError::Unauthorized),
+        None => return Ok(HttpResponse::BadRequest().body("This mod does not exist.")),
+    }
+
+    let description = data.description.as_deref().map(str::trim);
+
+    if let Some(description) = description {
+        if description.is_empty() || description.chars().count() > 1024 {
+            return Ok(HttpResponse::BadRequest()
+                .body("The description must be between 1 and 1024 characters."));
+        }
+    }
+
+    let homepage = data.homepage.as_deref().map(str::trim);
+    let documentation = data.documentation.as_deref().map(str::trim);
+
+    if let Some(url) = homepage {
+        check_url("homepage", url)?;
+    }
+
+    if let Some(url) = documentation {
+        check_url("documentation", url)?;
+    }
+
+    let keywords = data.keywords.as_deref().map(|i| {
+        i.split(',')
+            .map(str::trim)
+            .filter(|i| !i.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    });
+
+    if let Some(keywords) = &keywords {
+        if keywords.len() > 16 || keywords.iter().any(|i| i.chars().count() > 32) {
+            return Ok(HttpResponse::BadRequest()
+                .body("A mod can have up to 16 keywords of up to 32 characters."));
+        }
+    }
+
+    let repository_git = data.repository_git.as_deref().map(str::trim);
+    let repository_hg = data.repository_hg.as_deref().map(str::trim);
+
+    for (field, url) in &[("git", repository_git), ("hg", repository_hg)] {
+        if let Some(url) = url {
+            if url.is_empty() {
+                return Ok(
+                    HttpResponse::BadRequest().body("Repositories can be changed but not removed.")
+                );
+            }
+
+            check_url(&format!("{} repository", field), url)?;
+        }
+    }
+
+    // What changed, kept in the audit log as the history of the metadata.
+    let mut changes = BTreeMap::new();
+
+    for (field, value) in &[
+        ("description", description),
+        ("homepage", homepage),
+        ("documentation", documentation),
+        ("repository_git", repository_git),
+        ("repository_hg", repository_hg),
+    ] {
+        if let Some(value) = value {
+            changes.insert(*field, serde_json::json!(value));
+        }
+    }
+
+    if let Some(keywords) = &keywords {
+        changes.insert("keywords", serde_json::json!(keywords));
+    }
+
+    if changes.is_empty() {
+        return Ok(HttpResponse::BadRequest().body("Nothing to change."));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO mod_metadata
+                (mod_name, description, homepage, documentation, keywords, repository_git, repository_hg, updated_by)
+            VALUES
+                ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (mod_name) DO UPDATE SET
+                description = COALESCE($2, mod_metadata.description),
+                homepage = COALESCE($3, mod_metadata.homepage),
+                documentation = COALESCE($4, mod_metadata.documentation),
+                keywords = COALESCE($5, mod_metadata.keywords),
+                repository_git = COALESCE($6, mod_metadata.repository_git),
+                repository_hg = COALESCE($7, mod_metadata.repository_hg),
+                updated = CURRENT_TIMESTAMP,
+                updated_by = $8
+        "#,
+        &data.mod_name,
+        description,
+        homepage,
+        documentation,
+        keywords.as_deref(),
+        repository_git,
+        repository_hg,
+        principal.id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    AuditEntry {
+        action: "edit_metadata",
+        mod_name: Some(&data.mod_name),
+        details: Some(serde_json::to_string(&changes)?),
+        ..Default::default()
+    }
+    .log(principal.id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body("Successfully edited the metadata of the mod."))
+}
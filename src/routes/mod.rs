@@ -1,8 +1,15 @@
+pub mod admin;
+pub mod advisories;
+pub mod deprecation;
 pub mod download;
 pub mod get_mod;
 pub mod login;
+pub mod metadata;
+pub mod owners;
 pub mod search;
+pub mod sessions;
 pub mod teams;
+pub mod tokens;
 pub mod upload;
 pub mod users;
 pub mod verification;
@@ -0,0 +1,528 @@
+use crate::auth::Principal;
+use crate::error::*;
+use crate::model::*;
+use crate::utils::audit::AuditEntry;
+use crate::utils::ownership::acting_owner;
+use actix_web::{web, HttpResponse};
+use sqlx::{Executor, PgPool, Postgres};
+
+/// Days an ownership invite can be accepted for.
+const INVITE_DAYS: i32 = 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OwnersQueryData {
+    #[serde(rename = "mod")]
+    mod_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OwnerInfo {
+    id: String,
+    is_team: bool,
+    name: String,
+}
+
+/// Lists the owners of a mod.
+pub async fn list(
+    data: web::Query<OwnersQueryData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+
+    let owners = sqlx::query!(
+        r#"
+            SELECT
+                o.owner_id,
+                o.is_team,
+                COALESCE(t.name, u.username) as name
+            FROM
+                owners o
+                LEFT JOIN teams t ON o.is_team
+                AND t.id::BIGINT = o.owner_id
+                LEFT JOIN tokens u ON NOT o.is_team
+                AND u.owner_id = o.owner_id
+            WHERE
+                o.mod_name = $1
+            ORDER BY
+                o.is_team,
+                o.owner_id
+        "#,
+        &data.mod_name,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| {
+        let id = i.owner_id.to_string();
+
+        OwnerInfo {
+            name: i.name.unwrap_or_else(|| id.to_string()),
+            id,
+            is_team: i.is_team,
+        }
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(owners))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InviteOwnerData {
+    #[serde(rename = "mod")]
+    mod_name: String,
+    /// The user or team invited.
+    id: i64,
+    #[serde(default)]
+    is_team: bool,
+    /// Hand the mod over instead of sharing it, you stop being an owner when it's accepted.
+    #[serde(default)]
+    transfer: bool,
+}
+
+/// Invites a user or a team to become an owner of a mod, they have to accept it.
+///
+/// Owners of the mod and the Owners and Admins of teams that own it can invite.
+/// Inviting someone that already has an invite for the mod replaces it.
+pub async fn invite(
+    principal: Principal,
+    data: web::Form<InviteOwnerData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_write()?;
+    principal.require_scope(Scope::Publish)?;
+
+    let from_owner = if let Some(x) = acting_owner(principal.id, &data.mod_name, pool).await? {
+        x
+    } else {
+        return Err(ServiceError::Unauthorized);
+    };
+
+    let exists = if data.is_team {
+        sqlx::query!("SELECT id FROM teams WHERE id::BIGINT = $1", data.id)
+            .fetch_optional(pool)
+            .await?
+            .is_some()
+    } else {
+        sqlx::query!(
+            "SELECT owner_id FROM tokens WHERE owner_id = $1 AND NOT is_team",
+            data.id,
+        )
+        .fetch_optional(pool)
+        .await?
+        .is_some()
+    };
+
+    if !exists {
+        return Ok(HttpResponse::BadRequest().body("This user or team does not exist."));
+    }
+
+    let query = sqlx::query!(
+        "SELECT owner_id FROM owners WHERE mod_name = $1 AND owner_id = $2",
+        &data.mod_name,
+        data.id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.is_some() {
+        return Ok(HttpResponse::BadRequest().body("They already own this mod."));
+    }
+
+    let query = sqlx::query!(
+        "INSERT INTO ownership_invites (mod_name, from_owner, invited_by, invitee_id, invitee_is_team, transfer, expires)
+        VALUES ($1, $2, $3, $4, $5, $6, CURRENT_TIMESTAMP + make_interval(days => $7))
+        ON CONFLICT (mod_name, invitee_id) DO UPDATE
+        SET from_owner = EXCLUDED.from_owner, invited_by = EXCLUDED.invited_by, invitee_is_team = EXCLUDED.invitee_is_team,
+        transfer = EXCLUDED.transfer, created = CURRENT_TIMESTAMP, expires = EXCLUDED.expires
+        RETURNING id",
+        &data.mod_name,
+        from_owner,
+        principal.id,
+        data.id,
+        data.is_team,
+        data.transfer,
+        INVITE_DAYS,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    AuditEntry {
+        action: "invite_owner",
+        mod_name: Some(&data.mod_name),
+        target_id: Some(data.id),
+        details: Some(format!(
+            "Invite #{}{}",
+            query.id,
+            if data.transfer { ", transfer" } else { "" }
+        )),
+        ..Default::default()
+    }
+    .log(principal.id, pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Sent the invite with the id `{}`, it can be accepted for {} days.",
+        query.id, INVITE_DAYS
+    )))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OwnershipInviteInfo {
+    id: i32,
+    #[serde(rename = "mod")]
+    mod_name: String,
+    from_owner: String,
+    invited_by: String,
+    invitee_id: String,
+    invitee_is_team: bool,
+    transfer: bool,
+    created: String,
+    expires: String,
+    /// If the invite can be accepted by whoever requested the list, otherwise they sent it.
+    incoming: bool,
+}
+
+/// Lists the pending ownership invites sent to the user or their teams, and the ones they sent.
+pub async fn invites(principal: Principal, db: web::Data<PgPool>) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_scope(Scope::Read)?;
+
+    let invites = sqlx::query!(
+        r#"
+            SELECT
+                *,
+                (
+                    (i.invitee_id = $1 AND i.invitee_is_team = $2)
+                    OR (
+                        i.invitee_is_team
+                        AND EXISTS (
+                            SELECT 1 FROM team_members m
+                            WHERE m.team_id::BIGINT = i.invitee_id AND m.member = $1 AND (m.roles & $3) != 0
+                        )
+                    )
+                ) as "incoming!"
+            FROM
+                ownership_invites i
+            WHERE
+                i.expires > CURRENT_TIMESTAMP
+                AND (
+                    i.invited_by = $1
+                    OR (i.invitee_id = $1 AND i.invitee_is_team = $2)
+                    OR (
+                        i.invitee_is_team
+                        AND EXISTS (
+                            SELECT 1 FROM team_members m
+                            WHERE m.team_id::BIGINT = i.invitee_id AND m.member = $1 AND (m.roles & $3) != 0
+                        )
+                    )
+                )
+            ORDER BY
+                i.created DESC
+        "#,
+        principal.id,
+        principal.is_team,
+        (TeamRoles::OWNER | TeamRoles::ADMIN).bits() as i32,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| OwnershipInviteInfo {
+        id: i.id,
+        mod_name: i.mod_name,
+        from_owner: i.from_owner.to_string(),
+        invited_by: i.invited_by.to_string(),
+        invitee_id: i.invitee_id.to_string(),
+        invitee_is_team: i.invitee_is_team,
+        transfer: i.transfer,
+        created: i.created.to_rfc3339(),
+        expires: i.expires.to_rfc3339(),
+        incoming: i.incoming,
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(invites))
+}
+
+/// Checks if the principal can answer an invite for the user or team invited.
+///
+/// Teams answer through their token, or their Owners and Admins.
+async fn can_answer<'e, E>(
+    principal: &Principal,
+    invitee_id: i64,
+    invitee_is_team: bool,
+    executor: E,
+) -> ServiceResult<bool>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    if principal.id == invitee_id && principal.is_team == invitee_is_team {
+        return Ok(true);
+    }
+
+    if !invitee_is_team || principal.is_team {
+        return Ok(false);
+    }
+
+    let query = sqlx::query!(
+        "SELECT roles FROM team_members WHERE team_id::BIGINT = $1 AND member = $2",
+        invitee_id,
+        principal.id,
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(query
+        .map(|i| TeamRoles::from_bits_truncate(i.roles as u32).rank() >= TeamRoles::ADMIN.rank())
+        .unwrap_or(false))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InviteIdData {
+    id: i32,
+}
+
+/// Accepts an ownership invite, making the user or team an owner of the mod.
+pub async fn accept(
+    principal: Principal,
+    data: web::Form<InviteIdData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_write()?;
+    principal.require_scope(Scope::Publish)?;
+
+    let mut transaction = pool.begin().await?;
+
+    let query = sqlx::query!(
+        "SELECT * FROM ownership_invites WHERE id = $1 AND expires > CURRENT_TIMESTAMP FOR UPDATE",
+        data.id,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+
+    let invite = if let Some(x) = query {
+        x
+    } else {
+        return Ok(HttpResponse::BadRequest().body("This invite does not exist or has expired."));
+    };
+
+    if !can_answer(
+        &principal,
+        invite.invitee_id,
+        invite.invitee_is_team,
+        &mut transaction,
+    )
+    .await?
+    {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    sqlx::query!("DELETE FROM ownership_invites WHERE id = $1", invite.id)
+        .execute(&mut transaction)
+        .await?;
+
+    let query = sqlx::query!(
+        "SELECT owner_id FROM owners WHERE mod_name = $1 AND owner_id = $2",
+        &invite.mod_name,
+        invite.from_owner,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+
+    if query.is_none() {
+        transaction.commit().await?;
+
+        return Ok(HttpResponse::BadRequest()
+            .body("Whoever sent this invite doesn't own the mod anymore."));
+    }
+
+    // A transfer gives the new owner the place of the previous one.
+    sqlx::query!(
+        "INSERT INTO owners (owner_id, mod_name, checksums, is_team, added)
+        SELECT $1, $2, '{}', $3, CASE WHEN $4 THEN added ELSE CURRENT_TIMESTAMP END FROM owners WHERE mod_name = $2 AND owner_id = $5
+        ON CONFLICT DO NOTHING",
+        invite.invitee_id,
+        &invite.mod_name,
+        invite.invitee_is_team,
+        invite.transfer,
+        invite.from_owner,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    if invite.transfer {
+        sqlx::query!(
+            "DELETE FROM owners WHERE mod_name = $1 AND owner_id = $2",
+            &invite.mod_name,
+            invite.from_owner,
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM team_mod_permissions WHERE team_id::BIGINT = $1 AND mod_name = $2",
+            invite.from_owner,
+            &invite.mod_name,
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    AuditEntry {
+        action: if invite.transfer {
+            "transfer_mod"
+        } else {
+            "add_owner"
+        },
+        mod_name: Some(&invite.mod_name),
+        team_id: if invite.invitee_is_team {
+            Some(invite.invitee_id as i32)
+        } else {
+            None
+        },
+        target_id: Some(invite.invitee_id),
+        details: Some(format!("Invite #{} from {}", invite.id, invite.from_owner)),
+        ..Default::default()
+    }
+    .log(principal.id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    if invite.transfer {
+        Ok(HttpResponse::Ok().body(format!("`{}` has been transferred.", invite.mod_name)))
+    } else {
+        Ok(HttpResponse::Ok().body(format!("You are now an owner of `{}`.", invite.mod_name)))
+    }
+}
+
+/// Declines an ownership invite, or cancels it when done by whoever sent it.
+pub async fn decline(
+    principal: Principal,
+    data: web::Form<InviteIdData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_write()?;
+
+    let query = sqlx::query!(
+        "SELECT invited_by, invitee_id, invitee_is_team FROM ownership_invites WHERE id = $1",
+        data.id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let invite = if let Some(x) = query {
+        x
+    } else {
+        return Ok(HttpResponse::BadRequest().body("This invite does not exist."));
+    };
+
+    if invite.invited_by != principal.id
+        && !can_answer(&principal, invite.invitee_id, invite.invitee_is_team, pool).await?
+    {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    sqlx::query!("DELETE FROM ownership_invites WHERE id = $1", data.id)
+        .execute(pool)
+        .await?;
+
+    Ok(HttpResponse::Ok().body("Successfully removed the invite."))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoveOwnerData {
+    #[serde(rename = "mod")]
+    mod_name: String,
+    /// The owner removed, which can be yourself.
+    id: i64,
+}
+
+/// Removes an owner of a mod, a mod always keeps at least one owner.
+///
+/// Owners can remove themselves and the owners added after them, Admins and Owners of the site
+/// can remove anyone.
+pub async fn remove(
+    principal: Principal,
+    data: web::Form<RemoveOwnerData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_write()?;
+    principal.require_scope(Scope::Publish)?;
+
+    let acting = if let Some(x) = acting_owner(principal.id, &data.mod_name, pool).await? {
+        x
+    } else {
+        return Err(ServiceError::Unauthorized);
+    };
+
+    let mut transaction = pool.begin().await?;
+
+    let owners = sqlx::query!(
+        "SELECT owner_id, is_team, added FROM owners WHERE mod_name = $1 FOR UPDATE",
+        &data.mod_name,
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+
+    let owner = if let Some(x) = owners.iter().find(|i| i.owner_id == data.id) {
+        x
+    } else {
+        return Ok(HttpResponse::BadRequest().body("They don't own this mod."));
+    };
+
+    if owners.len() == 1 {
+        return Ok(
+            HttpResponse::BadRequest().body("A mod needs at least one owner, transfer it instead.")
+        );
+    }
+
+    let added_before = owners
+        .iter()
+        .find(|i| i.owner_id == acting)
+        .map(|i| i.added < owner.added)
+        .unwrap_or(false);
+
+    if owner.owner_id != acting
+        && !added_before
+        && !principal.roles.intersects(Roles::ADMIN | Roles::OWNER)
+    {
+        return Err(ServiceError::Forbidden(
+            "You can only remove yourself and the owners added after you.".into(),
+        ));
+    }
+
+    sqlx::query!(
+        "DELETE FROM owners WHERE mod_name = $1 AND owner_id = $2",
+        &data.mod_name,
+        data.id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    if owner.is_team {
+        sqlx::query!(
+            "DELETE FROM team_mod_permissions WHERE team_id::BIGINT = $1 AND mod_name = $2",
+            data.id,
+            &data.mod_name,
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    AuditEntry {
+        action: "remove_owner",
+        mod_name: Some(&data.mod_name),
+        target_id: Some(data.id),
+        ..Default::default()
+    }
+    .log(principal.id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body("Successfully removed the owner."))
+}
@@ -1,5 +1,7 @@
 use crate::error::ServiceResult;
 use crate::model::Verification;
+use crate::routes::advisories::{get_advisories, Advisory};
+use crate::routes::deprecation::{get_deprecations, Deprecation};
 use actix_web::{web, HttpResponse};
 use futures::StreamExt;
 use sqlx::types::chrono::{DateTime, Utc};
@@ -17,6 +19,7 @@ pub struct QueryData {
     pub verification: Option<Verification>,
     pub downloads: i64,
     pub uploaded: DateTime<Utc>,
+    pub yanked: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -30,6 +33,11 @@ pub struct SearchModsResponse {
     pub verification: Verification,
     pub downloads: i64,
     pub uploaded: String,
+    pub yanked: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub advisories: Vec<Advisory>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -50,6 +58,8 @@ pub struct SearchInfo {
     per_page: u8,
     #[serde(default)]
     verification: Verification,
+    #[serde(default)]
+    include_yanked: bool,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     before: Option<String>,
@@ -106,13 +116,17 @@ pub async fn search(
                 checksum,
                 name,
                 version,
-                description,
-                keywords,
+                COALESCE(md.description, mods.description) as "description!",
+                COALESCE(md.keywords, mods.keywords) as keywords,
                 verification as "verification: Verification",
                 downloads,
-                uploaded
+                uploaded,
+                yanked
             FROM
                 mods
+                LEFT JOIN mod_metadata md ON md.mod_name = mods.name
+            WHERE
+                NOT yanked OR $3
             ORDER BY
                 CASE WHEN $2 = 'asc' THEN
                     CASE $1
@@ -143,6 +157,7 @@ pub async fn search(
                 "desc"
             }
         },
+        data.include_yanked,
     )
     .fetch(pool)
     .boxed();
@@ -221,6 +236,9 @@ pub async fn search(
                     verification: values.verification.clone().unwrap_or_default(),
                     downloads: values.downloads,
                     uploaded: values.uploaded.to_rfc3339(),
+                    yanked: values.yanked,
+                    advisories: Vec::new(),
+                    deprecated: None,
                 });
             }
         }
@@ -230,6 +248,25 @@ pub async fn search(
         }
     }
 
+    drop(query);
+
+    let names = mods.iter().map(|i| i.name.to_string()).collect::<Vec<_>>();
+    let advisories = get_advisories(&names, pool).await?;
+    let deprecations = get_deprecations(&names, pool).await?;
+
+    for i in &mut mods {
+        i.deprecated = deprecations.get(&i.name).cloned();
+        i.advisories = advisories
+            .get(&i.name)
+            .map(|j| {
+                j.iter()
+                    .filter(|a| a.affects(&i.version))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
     if mods.is_empty() {
         Ok(HttpResponse::NoContent().finish())
     } else {
@@ -0,0 +1,99 @@
+use crate::auth::Principal;
+use crate::error::*;
+use crate::model::*;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SessionInfo {
+    id: i32,
+    provider: Option<String>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    created: String,
+    last_seen: String,
+    expires: String,
+    /// If this is the session the request was made with.
+    current: bool,
+}
+
+/// Lists the active sessions of the logged in user, requires the `read` scope.
+pub async fn list(principal: Principal, db: web::Data<PgPool>) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_scope(Scope::Read)?;
+    let user_id = principal.user_id()?;
+
+    let sessions = sqlx::query!(
+        "SELECT id, provider, ip, user_agent, created, last_seen, expires FROM sessions WHERE user_id = $1 AND expires > CURRENT_TIMESTAMP ORDER BY last_seen DESC",
+        user_id,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| SessionInfo {
+        current: Some(i.id) == principal.session_id,
+        id: i.id,
+        provider: i.provider,
+        ip: i.ip,
+        user_agent: i.user_agent,
+        created: i.created.to_rfc3339(),
+        last_seen: i.last_seen.to_rfc3339(),
+        expires: i.expires.to_rfc3339(),
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeSessionData {
+    id: i32,
+}
+
+/// Logs out a session of the logged in user, which can be on another device.
+pub async fn revoke(
+    principal: Principal,
+    data: web::Form<RevokeSessionData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    let query = sqlx::query!(
+        "DELETE FROM sessions WHERE id = $1 AND user_id = $2 RETURNING id",
+        data.id,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.is_some() {
+        Ok(HttpResponse::Ok().body("Successfully logged out the session."))
+    } else {
+        Ok(HttpResponse::BadRequest().body("This session does not exist."))
+    }
+}
+
+/// Logs out every session of the logged in user, except the one used for the request.
+pub async fn revoke_others(
+    principal: Principal,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    let query = sqlx::query!(
+        "DELETE FROM sessions WHERE user_id = $1 AND id != $2 RETURNING id",
+        user_id,
+        principal.session_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Successfully logged out {} other sessions.",
+        query.len()
+    )))
+}
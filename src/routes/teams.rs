@@ -1,54 +1,89 @@
+use crate::auth::Principal;
 use crate::error::*;
 use crate::model::*;
-use crate::utils::{self, tokens::gen_token};
-use actix_identity::Identity;
+use crate::routes::tokens::check_grace_period;
+use crate::utils::audit::AuditEntry;
+use crate::utils::ownership::{create_team_account, mod_permissions};
+use crate::utils::{
+    self,
+    tokens::{create_token, request_ip, rotate_token},
+};
 use actix_web::{web, HttpRequest, HttpResponse};
-use sqlx::PgPool;
+use handlebars::Handlebars;
+use semver::Version;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateTeamData {
     name: String,
 }
 
+/// Errors if the name can't be used for a team, `team_id` is the team being renamed.
+///
+/// Names are unique regardless of case.
+async fn check_team_name(name: &str, team_id: Option<i32>, pool: &PgPool) -> ServiceResult<()> {
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(ServiceError::BadRequest(
+            "Team names must be between 1 and 64 characters.".into(),
+        ));
+    }
+
+    let query = sqlx::query!(
+        "SELECT id FROM teams WHERE lower(name) = lower($1) AND ($2::INT IS NULL OR id != $2)",
+        name,
+        team_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.is_some() {
+        return Err(ServiceError::BadRequest(
+            "A team with the same name already exists".into(),
+        ));
+    }
+
+    Ok(())
+}
+
 pub async fn create_team(
-    id: Identity,
+    principal: Principal,
     data: web::Form<CreateTeamData>,
     db: web::Data<PgPool>,
 ) -> ServiceResult<HttpResponse> {
     let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
 
-    let query = sqlx::query!("SELECT * FROM teams WHERE name = $1", &data.name)
-        .fetch_optional(pool)
-        .await?;
+    let name = data.name.trim();
+    check_team_name(name, None, pool).await?;
 
-    if query.is_some() {
-        return Ok(HttpResponse::BadRequest().body("A team with the same name already exists"));
-    }
-
-    if let Some(user_id) = id.identity() {
-        let query = sqlx::query!(
-            "INSERT INTO teams (name) VALUES ($1) RETURNING id",
-            &data.name
-        )
+    let query = sqlx::query!("INSERT INTO teams (name) VALUES ($1) RETURNING id", name)
         .fetch_one(pool)
         .await?;
 
-        sqlx::query!(
-            "INSERT INTO team_members (team_id, member, roles) VALUES ($1, $2, $3)",
-            query.id,
-            user_id.parse::<i64>().unwrap(),
-            TeamRoles::OWNER.bits() as i32,
-        )
-        .execute(pool)
-        .await?;
+    sqlx::query!(
+        "INSERT INTO team_members (team_id, member, roles) VALUES ($1, $2, $3)",
+        query.id,
+        user_id,
+        TeamRoles::OWNER.bits() as i32,
+    )
+    .execute(pool)
+    .await?;
 
-        Ok(HttpResponse::Ok().body(&format!(
-            "Created the team `{}` with the id `{}`",
-            &data.name, query.id
-        )))
-    } else {
-        Ok(HttpResponse::BadRequest().body("No valid identity provided"))
+    AuditEntry {
+        action: "create_team",
+        team_id: Some(query.id),
+        details: Some(name.to_string()),
+        ..Default::default()
     }
+    .log(user_id, pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().body(&format!(
+        "Created the team `{}` with the id `{}`",
+        name, query.id
+    )))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -56,62 +91,168 @@ pub struct GetTokenData {
     id: u32,
 }
 
+/// The name of the team token given to a member, each member has their own so it can be revoked
+/// when they leave.
+pub fn member_token_name(user_id: i64) -> String {
+    format!("member:{}", user_id)
+}
+
+/// Revokes the team token of a member, when they leave the team or can't hold one anymore.
+async fn revoke_member_token(
+    team_id: i32,
+    member: i64,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> ServiceResult<()> {
+    sqlx::query!(
+        "WITH revoked AS (DELETE FROM api_tokens WHERE owner_id = $1 AND name = $2 RETURNING id, name)
+        INSERT INTO token_events (owner_id, token_id, token_name, event) SELECT $1, id, name, 'revoked' FROM revoked",
+        team_id as i64,
+        member_token_name(member),
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns a token of the team for the member, replacing the one they got before.
+///
+/// Only the owners and admins of the team can get one, it's revoked when they leave the team or
+/// are demoted. Only a hash of the token is stored, so it can only be shown when it is generated.
 pub async fn get_token(
-    id: Identity,
-    data: web::Query<GetTokenData>,
+    req: HttpRequest,
+    principal: Principal,
+    data: web::Form<GetTokenData>,
     db: web::Data<PgPool>,
-    config: web::Data<Config>,
 ) -> ServiceResult<HttpResponse> {
     let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
 
-    if let Some(user_id) = id.identity() {
-        let user_id = user_id.parse::<i64>().unwrap();
-        let query = sqlx::query!(
-            "SELECT * FROM team_members WHERE team_id = $1 AND member = $2",
-            data.id as i32,
-            user_id,
-        )
-        .fetch_optional(pool)
-        .await?;
+    // The token can do everything with the mods of the team, like its owners and admins.
+    match get_member_roles(data.id as i32, user_id, pool).await? {
+        Some(roles) if roles.intersects(TeamRoles::OWNER | TeamRoles::ADMIN) => (),
+        _ => return Err(ServiceError::Unauthorized),
+    }
+
+    let query = sqlx::query!(
+        r#"SELECT (is_banned AND (ban_expires IS NULL OR ban_expires > CURRENT_TIMESTAMP)) as "is_banned!" FROM tokens WHERE owner_id = $1 AND is_team = true"#,
+        data.id as i64,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(data) = query {
+        if data.is_banned {
+            return Ok(HttpResponse::Forbidden().body("This team has been banned."));
+        }
+    } else {
+        create_team_account(data.id as i64, pool).await?;
+    }
+
+    let name = member_token_name(user_id);
+    let ip = request_ip(&req);
+
+    let query = sqlx::query!(
+        "SELECT id FROM api_tokens WHERE owner_id = $1 AND name = $2",
+        data.id as i64,
+        &name,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let token = match query {
+        Some(x) => rotate_token(data.id as i64, x.id, 0, ip.as_deref(), pool).await?,
+        None => None,
+    };
+
+    let token = match token {
+        Some(token) => token,
+        None => {
+            create_token(
+                data.id as i64,
+                &name,
+                &[Scope::Publish, Scope::Yank, Scope::Read],
+                None,
+                ip.as_deref(),
+                pool,
+            )
+            .await?
+        }
+    };
+
+    Ok(HttpResponse::Ok().body(token))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateTokenData {
+    id: u32,
+    /// Hours the old token keeps working for, it stops working immediately when missing.
+    grace_period: Option<i32>,
+}
+
+/// Replaces the team token of the member, the new token is only shown in this response.
+///
+/// Only the owners and admins of the team can rotate their token.
+pub async fn rotate_team_token(
+    req: HttpRequest,
+    principal: Principal,
+    data: web::Form<RotateTokenData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+
+    if let Some(resp) = check_grace_period(data.grace_period) {
+        return Ok(resp);
+    }
+
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    let query = sqlx::query!(
+        "SELECT roles FROM team_members WHERE team_id = $1 AND member = $2",
+        data.id as i32,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(member) = query {
+        let roles = TeamRoles::from_bits_truncate(member.roles as u32);
 
-        if query.is_some() {
+        if roles.intersects(TeamRoles::OWNER | TeamRoles::ADMIN) {
             let query = sqlx::query!(
-                "SELECT token, is_banned FROM tokens WHERE owner_id = $1 AND is_team = true",
+                "SELECT id FROM api_tokens WHERE owner_id = $1 AND name = $2",
                 data.id as i64,
+                member_token_name(user_id),
             )
             .fetch_optional(pool)
             .await?;
 
-            if let Some(data) = query {
-                if data.is_banned {
-                    return Ok(HttpResponse::Ok().body("Team has been banned."));
-                } else {
-                    return Ok(HttpResponse::Ok().body(data.token));
-                }
+            let token_id = if let Some(x) = query {
+                x.id
             } else {
-                let token = gen_token(
-                    user_id as u64,
-                    &format!("{}@local", user_id),
-                    &hex::decode(&config.secret_key).unwrap(),
-                    &hex::decode(&config.iv_key).unwrap(),
-                )
-                .unwrap_or_else(|| "null".to_string());
+                return Ok(
+                    HttpResponse::BadRequest().body("You don't have a token of this team yet.")
+                );
+            };
 
-                sqlx::query!(
-                    "INSERT INTO tokens (owner_id, email, token, is_team) VALUES ($1, $2, $3, true)",
-                    data.id as i64,
-                    &format!("{}@local", user_id),
-                    &token
-                )
-                .execute(pool)
-                .await?;
+            let token = rotate_token(
+                data.id as i64,
+                token_id,
+                data.grace_period.unwrap_or(0),
+                request_ip(&req).as_deref(),
+                pool,
+            )
+            .await?;
 
+            if let Some(token) = token {
                 return Ok(HttpResponse::Ok().body(token));
             }
         }
     }
 
-    Ok(HttpResponse::Ok().body("null"))
+    Err(ServiceError::Unauthorized)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -122,47 +263,66 @@ pub struct TransferModData {
 }
 
 pub async fn transfer_mod(
-    id: Identity,
-    data: web::Query<TransferModData>,
+    principal: Principal,
+    data: web::Form<TransferModData>,
     db: web::Data<PgPool>,
 ) -> ServiceResult<HttpResponse> {
     let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
 
-    if let Some(user_id) = id.identity() {
-        let user_id = user_id.parse::<i64>().unwrap();
+    let query = sqlx::query!(
+        "SELECT * FROM owners WHERE owner_id = $1 AND mod_name = $2",
+        user_id,
+        &data.mod_name,
+    )
+    .fetch_optional(pool)
+    .await?;
 
+    if query.is_some() {
         let query = sqlx::query!(
-            "SELECT * FROM owners WHERE owner_id = $1 AND mod_name = $2",
+            "SELECT * FROM team_members WHERE team_id = $1 AND member = $2",
+            data.team_id,
             user_id,
-            &data.mod_name,
         )
         .fetch_optional(pool)
         .await?;
 
-        if query.is_some() {
-            let query = sqlx::query!(
-                "SELECT * FROM team_members WHERE team_id = $1 AND member = $2",
-                data.team_id,
-                user_id,
-            )
-            .fetch_optional(pool)
-            .await?;
+        if let Some(user) = query {
+            let roles = TeamRoles::from_bits_truncate(user.roles as u32);
+
+            if roles.contains(TeamRoles::OWNER) {
+                let query = sqlx::query!(
+                    "SELECT owner_id FROM owners WHERE owner_id = $1 AND mod_name = $2",
+                    data.team_id as i64,
+                    &data.mod_name,
+                )
+                .fetch_optional(pool)
+                .await?;
 
-            if let Some(user) = query {
-                let roles = TeamRoles::from_bits_truncate(user.roles as u32);
+                if query.is_some() {
+                    return Ok(HttpResponse::BadRequest().body("The team already owns this mod."));
+                }
 
-                if roles.contains(TeamRoles::OWNER) {
-                    sqlx::query!(
-                        "UPDATE owners SET owner_id = $1, is_team = true WHERE owner_id = $2 AND mod_name = $3",
-                        data.team_id as i64,
-                        user_id,
-                        &data.mod_name,
-                    )
-                    .execute(pool)
-                    .await?;
+                sqlx::query!(
+                    "UPDATE owners SET owner_id = $1, is_team = true WHERE owner_id = $2 AND mod_name = $3",
+                    data.team_id as i64,
+                    user_id,
+                    &data.mod_name,
+                )
+                .execute(pool)
+                .await?;
 
-                    return Ok(HttpResponse::Ok().body("Transfer succeed!"));
+                AuditEntry {
+                    action: "transfer_mod",
+                    mod_name: Some(&data.mod_name),
+                    team_id: Some(data.team_id),
+                    ..Default::default()
                 }
+                .log(user_id, pool)
+                .await?;
+
+                return Ok(HttpResponse::Ok().body("Transfer succeed!"));
             }
         }
     }
@@ -173,107 +333,1259 @@ pub async fn transfer_mod(
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InviteCreateData {
     team_id: i32,
+    /// Days until the invite expires, 7 when missing.
+    days: Option<i32>,
+    /// Times the invite can be used, unlimited when missing.
+    max_uses: Option<i32>,
+    /// The only user that can join with the invite.
+    target: Option<i64>,
+    /// `admin`, `mod` or `member`, the role members joining with the invite get.
+    role: Option<String>,
 }
 
+/// Creates an invite link for a team.
+///
+/// Mods, Admins and Owners can invite, but only Admins and Owners can invite with a role, below
+/// their own.
 pub async fn invite(
     req: HttpRequest,
-    id: Identity,
-    data: web::Query<InviteCreateData>,
+    principal: Principal,
+    data: web::Form<InviteCreateData>,
     db: web::Data<PgPool>,
 ) -> ServiceResult<HttpResponse> {
     let pool = &**db;
+    principal.require_write()?;
+    let user_id = principal.user_id()?;
 
-    if let Some(user_id) = id.identity() {
-        let user_id = user_id.parse::<i64>().unwrap();
+    let days = data.days.unwrap_or(7);
 
-        let query = sqlx::query!(
-            "SELECT * FROM team_members WHERE team_id = $1 AND member = $2",
+    if !(1..=30).contains(&days) {
+        return Ok(HttpResponse::BadRequest().body("Invites can last between 1 and 30 days."));
+    }
+
+    if let Some(max_uses) = data.max_uses {
+        if max_uses < 1 {
+            return Ok(HttpResponse::BadRequest().body("Invites need to have at least 1 use."));
+        }
+    }
+
+    let role = match data.role.as_deref().map(str::parse::<TeamRoles>) {
+        Some(Ok(x)) => x,
+        Some(Err(why)) => return Ok(HttpResponse::BadRequest().body(why)),
+        None => TeamRoles::empty(),
+    };
+
+    let roles = match get_member_roles(data.team_id, user_id, pool).await? {
+        Some(x) if x.rank() >= TeamRoles::MOD.rank() => x,
+        Some(_) => {
+            return Err(ServiceError::Forbidden(
+                "Only Mods, Admins and Owners can invite to the team.".into(),
+            ))
+        }
+        None => return Err(ServiceError::Unauthorized),
+    };
+
+    if !role.is_empty() {
+        check_team_rank(roles, role)?;
+    }
+
+    let mut code = utils::invite::create();
+
+    while sqlx::query!("SELECT id FROM team_invites WHERE code = $1", &code)
+        .fetch_optional(pool)
+        .await?
+        .is_some()
+    {
+        code = utils::invite::create();
+    }
+
+    let query = sqlx::query!(
+        "INSERT INTO team_invites (team_id, code, created_by, target_user, roles, max_uses, expires) VALUES ($1, $2, $3, $4, $5, $6, CURRENT_TIMESTAMP + make_interval(days => $7)) RETURNING id",
+        data.team_id,
+        &code,
+        user_id,
+        data.target,
+        role.bits() as i32,
+        data.max_uses,
+        days,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    AuditEntry {
+        action: "create_invite",
+        team_id: Some(data.team_id),
+        target_id: data.target,
+        details: Some(format!("Invite #{}", query.id)),
+        ..Default::default()
+    }
+    .log(user_id, pool)
+    .await?;
+
+    let conn = req.connection_info();
+
+    Ok(HttpResponse::Ok().body(format!(
+        "{}://{}/teams/join/{}",
+        conn.scheme(),
+        conn.host(),
+        code
+    )))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InviteUse {
+    user_id: String,
+    username: String,
+    created: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InviteInfo {
+    id: i32,
+    code: String,
+    created_by: Option<String>,
+    target_user: Option<String>,
+    roles: i32,
+    max_uses: Option<i32>,
+    uses: i32,
+    expires: Option<String>,
+    created: String,
+    /// If the invite can still be used.
+    active: bool,
+    /// Who joined the team with the invite.
+    joined: Vec<InviteUse>,
+}
+
+/// Lists the invites of a team and who joined with them, newest first.
+///
+/// Admins and Owners see every invite, other members only the ones they created.
+pub async fn invites(
+    principal: Principal,
+    data: web::Query<TeamIdData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_scope(Scope::Read)?;
+    let user_id = principal.user_id()?;
+
+    let roles = if let Some(x) = get_member_roles(data.team_id, user_id, pool).await? {
+        x
+    } else {
+        return Err(ServiceError::Unauthorized);
+    };
+
+    let mut joined = HashMap::new();
+
+    let query = sqlx::query!(
+        "SELECT u.invite_id, u.user_id, u.created, t.username FROM team_invite_uses u JOIN team_invites i ON i.id = u.invite_id LEFT JOIN tokens t ON t.owner_id = u.user_id WHERE i.team_id = $1 ORDER BY u.created",
+        data.team_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for i in query {
+        let user_id = i.user_id.to_string();
+
+        joined
+            .entry(i.invite_id)
+            .or_insert_with(Vec::new)
+            .push(InviteUse {
+                username: i.username.unwrap_or_else(|| user_id.to_string()),
+                user_id,
+                created: i.created.to_rfc3339(),
+            });
+    }
+
+    let invites = sqlx::query!(
+        r#"
+            SELECT
+                id,
+                code,
+                created_by,
+                target_user,
+                roles,
+                max_uses,
+                uses,
+                expires,
+                created,
+                (
+                    NOT revoked
+                    AND (expires IS NULL OR expires > CURRENT_TIMESTAMP)
+                    AND (max_uses IS NULL OR uses < max_uses)
+                ) as "active!"
+            FROM
+                team_invites
+            WHERE
+                team_id = $1
+                AND ($2 OR created_by = $3)
+            ORDER BY id DESC
+            LIMIT 100
+        "#,
+        data.team_id,
+        roles.rank() >= TeamRoles::ADMIN.rank(),
+        user_id,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| InviteInfo {
+        joined: joined.remove(&i.id).unwrap_or_default(),
+        id: i.id,
+        code: i.code,
+        created_by: i.created_by.map(|x| x.to_string()),
+        target_user: i.target_user.map(|x| x.to_string()),
+        roles: i.roles,
+        max_uses: i.max_uses,
+        uses: i.uses,
+        expires: i.expires.map(|x| x.to_rfc3339()),
+        created: i.created.to_rfc3339(),
+        active: i.active,
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(invites))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeInviteData {
+    team_id: i32,
+    id: i32,
+}
+
+/// Revokes an invite, members can revoke their own invites and Admins and Owners any of them.
+pub async fn revoke_invite(
+    principal: Principal,
+    data: web::Form<RevokeInviteData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_write()?;
+    let user_id = principal.user_id()?;
+
+    let roles = if let Some(x) = get_member_roles(data.team_id, user_id, pool).await? {
+        x
+    } else {
+        return Err(ServiceError::Unauthorized);
+    };
+
+    let query = sqlx::query!(
+        "UPDATE team_invites SET revoked = true WHERE id = $1 AND team_id = $2 AND NOT revoked AND ($3 OR created_by = $4) RETURNING id",
+        data.id,
+        data.team_id,
+        roles.rank() >= TeamRoles::ADMIN.rank(),
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.is_none() {
+        return Ok(
+            HttpResponse::BadRequest().body("This invite does not exist, or was already revoked.")
+        );
+    }
+
+    AuditEntry {
+        action: "revoke_invite",
+        team_id: Some(data.team_id),
+        details: Some(format!("Invite #{}", data.id)),
+        ..Default::default()
+    }
+    .log(user_id, pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().body("Successfully revoked the invite."))
+}
+
+/// The page invite links lead to, joining is done with a POST from it so other sites can't make
+/// users join teams.
+pub async fn join_page(
+    data: web::Path<String>,
+    hb: web::Data<Handlebars<'_>>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+
+    let query = sqlx::query!(
+        "SELECT t.name FROM team_invites i JOIN teams t ON t.id = i.team_id WHERE i.code = $1 AND NOT i.revoked AND (i.expires IS NULL OR i.expires > CURRENT_TIMESTAMP) AND (i.max_uses IS NULL OR i.uses < i.max_uses)",
+        &*data,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(team) = query {
+        let data = serde_json::json!({
+            "team": team.name,
+            "invite": &*data,
+        });
+
+        let body = hb.render("join_team", &data).unwrap();
+
+        Ok(HttpResponse::Ok().body(body))
+    } else {
+        Ok(HttpResponse::NotFound().body("This invite does not exist or has expired."))
+    }
+}
+
+pub async fn join(
+    principal: Principal,
+    data: web::Path<String>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_write()?;
+    let user_id = principal.user_id()?;
+
+    let mut transaction = pool.begin().await?;
+
+    let query = sqlx::query!(
+        "SELECT i.id, i.team_id, i.target_user, i.roles, t.name FROM team_invites i JOIN teams t ON t.id = i.team_id WHERE i.code = $1 AND NOT i.revoked AND (i.expires IS NULL OR i.expires > CURRENT_TIMESTAMP) AND (i.max_uses IS NULL OR i.uses < i.max_uses) FOR UPDATE OF i",
+        &*data,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+
+    let invite = if let Some(x) = query {
+        x
+    } else {
+        return Ok(HttpResponse::BadRequest().body("This invite does not exist or has expired."));
+    };
+
+    if invite.target_user.map(|i| i != user_id).unwrap_or(false) {
+        return Err(ServiceError::Forbidden(
+            "This invite is for another user.".into(),
+        ));
+    }
+
+    // The invite is locked, so parallel joins with it wait for this one and can't use it more than
+    // it allows.
+    let query = sqlx::query!(
+        "INSERT INTO team_members (team_id, member, roles) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING RETURNING member",
+        invite.team_id,
+        user_id,
+        invite.roles,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+
+    if query.is_none() {
+        return Ok(HttpResponse::BadRequest().body("You are already a member of this team."));
+    }
+
+    sqlx::query!(
+        "UPDATE team_invites SET uses = uses + 1 WHERE id = $1",
+        invite.id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO team_invite_uses (invite_id, user_id) VALUES ($1, $2)",
+        invite.id,
+        user_id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    AuditEntry {
+        action: "join_team",
+        team_id: Some(invite.team_id),
+        details: Some(format!("Invite #{}", invite.id)),
+        ..Default::default()
+    }
+    .log(user_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Successfully joined team {} with id {}",
+        invite.name, invite.team_id
+    )))
+}
+
+/// Gets the roles of a member of a team, `None` if they are not a member.
+async fn get_member_roles(
+    team_id: i32,
+    member: i64,
+    pool: &PgPool,
+) -> ServiceResult<Option<TeamRoles>> {
+    let query = sqlx::query!(
+        "SELECT roles FROM team_members WHERE team_id = $1 AND member = $2",
+        team_id,
+        member,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(query.map(|i| TeamRoles::from_bits_truncate(i.roles as u32)))
+}
+
+/// Errors unless the member can manage the target, Admins and Owners can manage the members
+/// below their own rank.
+fn check_team_rank(roles: TeamRoles, target: TeamRoles) -> ServiceResult<()> {
+    if roles.rank() >= TeamRoles::ADMIN.rank() && roles.rank() > target.rank() {
+        Ok(())
+    } else {
+        Err(ServiceError::Forbidden(
+            "You can only manage members below your own rank.".into(),
+        ))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeamIdData {
+    team_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TeamMemberInfo {
+    id: String,
+    username: String,
+    roles: i32,
+}
+
+/// Lists the members of a team, only for its members.
+pub async fn members(
+    principal: Principal,
+    data: web::Query<TeamIdData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_scope(Scope::Read)?;
+    let user_id = principal.user_id()?;
+
+    if get_member_roles(data.team_id, user_id, pool)
+        .await?
+        .is_none()
+    {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    let mut members = sqlx::query!(
+        "SELECT m.member, m.roles, t.username FROM team_members m JOIN tokens t ON t.owner_id = m.member WHERE m.team_id = $1",
+        data.team_id,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| {
+        let id = i.member.to_string();
+
+        TeamMemberInfo {
+            username: i.username.unwrap_or_else(|| id.to_string()),
+            id,
+            roles: i.roles,
+        }
+    })
+    .collect::<Vec<_>>();
+
+    members
+        .sort_by_key(|i| std::cmp::Reverse(TeamRoles::from_bits_truncate(i.roles as u32).rank()));
+
+    Ok(HttpResponse::Ok().json(members))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeamMemberData {
+    team_id: i32,
+    member: i64,
+}
+
+/// Removes a member from a team.
+pub async fn kick(
+    principal: Principal,
+    data: web::Form<TeamMemberData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    if data.member == user_id {
+        return Ok(HttpResponse::BadRequest().body("Leave the team instead."));
+    }
+
+    let roles = if let Some(x) = get_member_roles(data.team_id, user_id, pool).await? {
+        x
+    } else {
+        return Err(ServiceError::Unauthorized);
+    };
+
+    let target = if let Some(x) = get_member_roles(data.team_id, data.member, pool).await? {
+        x
+    } else {
+        return Ok(HttpResponse::BadRequest().body("This user is not a member of the team."));
+    };
+
+    check_team_rank(roles, target)?;
+
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM team_members WHERE team_id = $1 AND member = $2",
+        data.team_id,
+        data.member,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM team_mod_permissions WHERE team_id = $1 AND member = $2",
+        data.team_id,
+        data.member,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    revoke_member_token(data.team_id, data.member, &mut transaction).await?;
+
+    AuditEntry {
+        action: "kick_member",
+        team_id: Some(data.team_id),
+        target_id: Some(data.member),
+        ..Default::default()
+    }
+    .log(user_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body("Successfully removed the member."))
+}
+
+/// Leaves a team, the owner has to transfer the ownership or delete the team instead.
+pub async fn leave(
+    principal: Principal,
+    data: web::Form<TeamIdData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    let roles = if let Some(x) = get_member_roles(data.team_id, user_id, pool).await? {
+        x
+    } else {
+        return Ok(HttpResponse::BadRequest().body("You are not a member of this team."));
+    };
+
+    if roles.contains(TeamRoles::OWNER) {
+        return Ok(HttpResponse::BadRequest()
+            .body("The owner can't leave the team, transfer it or delete it instead."));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM team_members WHERE team_id = $1 AND member = $2",
+        data.team_id,
+        user_id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM team_mod_permissions WHERE team_id = $1 AND member = $2",
+        data.team_id,
+        user_id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    revoke_member_token(data.team_id, user_id, &mut transaction).await?;
+
+    AuditEntry {
+        action: "leave_team",
+        team_id: Some(data.team_id),
+        ..Default::default()
+    }
+    .log(user_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body("Successfully left the team."))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeamRoleData {
+    team_id: i32,
+    member: i64,
+    /// `admin`, `mod` or `member`.
+    role: String,
+}
+
+/// Promotes or demotes a member, to a role below the rank of whoever is changing it.
+pub async fn set_role(
+    principal: Principal,
+    data: web::Form<TeamRoleData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    let role = match data.role.parse::<TeamRoles>() {
+        Ok(x) => x,
+        Err(why) => return Ok(HttpResponse::BadRequest().body(why)),
+    };
+
+    let roles = if let Some(x) = get_member_roles(data.team_id, user_id, pool).await? {
+        x
+    } else {
+        return Err(ServiceError::Unauthorized);
+    };
+
+    let target = if let Some(x) = get_member_roles(data.team_id, data.member, pool).await? {
+        x
+    } else {
+        return Ok(HttpResponse::BadRequest().body("This user is not a member of the team."));
+    };
+
+    check_team_rank(roles, target)?;
+    check_team_rank(roles, role)?;
+
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE team_members SET roles = $3 WHERE team_id = $1 AND member = $2",
+        data.team_id,
+        data.member,
+        role.bits() as i32,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    if !role.intersects(TeamRoles::OWNER | TeamRoles::ADMIN) {
+        revoke_member_token(data.team_id, data.member, &mut transaction).await?;
+    }
+
+    AuditEntry {
+        action: "set_team_role",
+        team_id: Some(data.team_id),
+        target_id: Some(data.member),
+        details: Some(data.role.to_lowercase()),
+        ..Default::default()
+    }
+    .log(user_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Successfully changed the role to `{}`.",
+        data.role.to_lowercase()
+    )))
+}
+
+/// Makes another member the owner of the team, the previous owner becomes an Admin.
+pub async fn transfer_ownership(
+    principal: Principal,
+    data: web::Form<TeamMemberData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    match get_member_roles(data.team_id, user_id, pool).await? {
+        Some(roles) if roles.contains(TeamRoles::OWNER) => (),
+        _ => return Err(ServiceError::Unauthorized),
+    }
+
+    if data.member == user_id
+        || get_member_roles(data.team_id, data.member, pool)
+            .await?
+            .is_none()
+    {
+        return Ok(HttpResponse::BadRequest().body("This user is not a member of the team."));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE team_members SET roles = $3 WHERE team_id = $1 AND member = $2",
+        data.team_id,
+        data.member,
+        TeamRoles::OWNER.bits() as i32,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE team_members SET roles = $3 WHERE team_id = $1 AND member = $2",
+        data.team_id,
+        user_id,
+        TeamRoles::ADMIN.bits() as i32,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    AuditEntry {
+        action: "transfer_team",
+        team_id: Some(data.team_id),
+        target_id: Some(data.member),
+        ..Default::default()
+    }
+    .log(user_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body("Successfully transferred the team."))
+}
+
+/// Deletes a team, only its owner can.
+///
+/// The mods only the team owns are transferred to the owner, and the team token stops working.
+/// Banned teams can't be deleted, so the ban can't be dodged.
+pub async fn delete_team(
+    principal: Principal,
+    data: web::Form<TeamIdData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    match get_member_roles(data.team_id, user_id, pool).await? {
+        Some(roles) if roles.contains(TeamRoles::OWNER) => (),
+        _ => return Err(ServiceError::Unauthorized),
+    }
+
+    let query = sqlx::query!(
+        r#"SELECT (is_banned AND (ban_expires IS NULL OR ban_expires > CURRENT_TIMESTAMP)) as "is_banned!" FROM tokens WHERE owner_id = $1 AND is_team = true"#,
+        data.team_id as i64,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.map(|i| i.is_banned).unwrap_or(false) {
+        return Err(ServiceError::Forbidden(
+            "Banned teams can't be deleted.".into(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    // Mods with other owners stay with them, and the owner of the team could be one of them.
+    sqlx::query!(
+        "DELETE FROM owners o WHERE owner_id = $1 AND is_team AND EXISTS (SELECT 1 FROM owners x WHERE x.mod_name = o.mod_name AND x.owner_id != $1)",
+        data.team_id as i64,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let mods = sqlx::query!(
+        "UPDATE owners SET owner_id = $2, is_team = false WHERE owner_id = $1 AND is_team RETURNING mod_name",
+        data.team_id as i64,
+        user_id,
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+
+    sqlx::query!("DELETE FROM team_members WHERE team_id = $1", data.team_id)
+        .execute(&mut transaction)
+        .await?;
+
+    // The tokens of the team are deleted with its account.
+    sqlx::query!(
+        "DELETE FROM tokens WHERE owner_id = $1 AND is_team",
+        data.team_id as i64,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!("DELETE FROM teams WHERE id = $1", data.team_id)
+        .execute(&mut transaction)
+        .await?;
+
+    AuditEntry {
+        action: "delete_team",
+        team_id: Some(data.team_id),
+        details: Some(format!(
+            "Mods transferred to the owner: {}",
+            mods.iter()
+                .map(|i| i.mod_name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        ..Default::default()
+    }
+    .log(user_id, &mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Successfully deleted the team, {} mods were transferred to you.",
+        mods.len()
+    )))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeamModData {
+    team_id: i32,
+    #[serde(rename = "mod")]
+    mod_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MemberModPermissions {
+    id: String,
+    username: String,
+    roles: i32,
+    permissions: u32,
+    /// If the permissions were set for the mod, instead of coming from the team roles.
+    custom: bool,
+}
+
+/// Checks that a mod is owned by a team.
+async fn is_team_mod(team_id: i32, mod_name: &str, pool: &PgPool) -> ServiceResult<bool> {
+    let query = sqlx::query!(
+        "SELECT mod_name FROM owners WHERE owner_id = $1 AND mod_name = $2 AND is_team",
+        team_id as i64,
+        mod_name,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(query.is_some())
+}
+
+/// Lists what each member of a team can do with one of the mods of the team.
+pub async fn mod_permissions_list(
+    principal: Principal,
+    data: web::Query<TeamModData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_scope(Scope::Read)?;
+    let user_id = principal.user_id()?;
+
+    if get_member_roles(data.team_id, user_id, pool)
+        .await?
+        .is_none()
+    {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    if !is_team_mod(data.team_id, &data.mod_name, pool).await? {
+        return Ok(HttpResponse::BadRequest().body("This mod is not owned by the team."));
+    }
+
+    let members = sqlx::query!(
+        r#"
+            SELECT
+                m.member,
+                m.roles,
+                t.username,
+                p.permissions as "permissions?"
+            FROM
+                team_members m
+                JOIN tokens t ON t.owner_id = m.member
+                LEFT JOIN team_mod_permissions p ON p.team_id = m.team_id
+                AND p.member = m.member
+                AND p.mod_name = $2
+            WHERE
+                m.team_id = $1
+        "#,
+        data.team_id,
+        &data.mod_name,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| {
+        let id = i.member.to_string();
+        let roles = TeamRoles::from_bits_truncate(i.roles as u32);
+
+        MemberModPermissions {
+            username: i.username.unwrap_or_else(|| id.to_string()),
+            id,
+            roles: i.roles,
+            permissions: i
+                .permissions
+                .map(|x| ModPermissions::from_bits_truncate(x as u32))
+                .unwrap_or_else(|| roles.mod_permissions())
+                .bits(),
+            custom: i.permissions.is_some(),
+        }
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(members))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetModPermissionsData {
+    team_id: i32,
+    #[serde(rename = "mod")]
+    mod_name: String,
+    member: i64,
+    /// Comma separated `publish`, `yank` and `manage`, or `default` for the ones of the team
+    /// roles of the member.
+    permissions: String,
+}
+
+/// Sets what a member can do with a mod of the team.
+///
+/// Requires the `manage` permission over the mod, and only works on members below your rank.
+/// Permissions you don't have yourself can't be given.
+pub async fn set_mod_permissions(
+    principal: Principal,
+    data: web::Form<SetModPermissionsData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    let permissions = if data.permissions.eq_ignore_ascii_case("default") {
+        None
+    } else {
+        match data.permissions.parse::<ModPermissions>() {
+            Ok(x) => Some(x),
+            Err(why) => return Ok(HttpResponse::BadRequest().body(why)),
+        }
+    };
+
+    let roles = if let Some(x) = get_member_roles(data.team_id, user_id, pool).await? {
+        x
+    } else {
+        return Err(ServiceError::Unauthorized);
+    };
+
+    if !is_team_mod(data.team_id, &data.mod_name, pool).await? {
+        return Ok(HttpResponse::BadRequest().body("This mod is not owned by the team."));
+    }
+
+    let own_permissions = mod_permissions(user_id, &data.mod_name, pool)
+        .await?
+        .unwrap_or_else(ModPermissions::empty);
+
+    if !own_permissions.contains(ModPermissions::MANAGE) {
+        return Err(ServiceError::Forbidden(
+            "You can't manage the permissions of this mod.".into(),
+        ));
+    }
+
+    let target = if let Some(x) = get_member_roles(data.team_id, data.member, pool).await? {
+        x
+    } else {
+        return Ok(HttpResponse::BadRequest().body("This user is not a member of the team."));
+    };
+
+    if roles.rank() <= target.rank() {
+        return Err(ServiceError::Forbidden(
+            "You can only manage members below your own rank.".into(),
+        ));
+    }
+
+    if !own_permissions.contains(permissions.unwrap_or_else(|| target.mod_permissions())) {
+        return Err(ServiceError::Forbidden(
+            "You can't give permissions you don't have.".into(),
+        ));
+    }
+
+    if let Some(permissions) = permissions {
+        sqlx::query!(
+            "INSERT INTO team_mod_permissions (team_id, mod_name, member, permissions) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (team_id, mod_name, member) DO UPDATE SET permissions = EXCLUDED.permissions",
             data.team_id,
-            user_id,
+            &data.mod_name,
+            data.member,
+            permissions.bits() as i32,
         )
-        .fetch_optional(pool)
+        .execute(pool)
+        .await?;
+    } else {
+        sqlx::query!(
+            "DELETE FROM team_mod_permissions WHERE team_id = $1 AND mod_name = $2 AND member = $3",
+            data.team_id,
+            &data.mod_name,
+            data.member,
+        )
+        .execute(pool)
         .await?;
+    }
 
-        if let Some(_user) = query {
-            let query = sqlx::query!("SELECT invite FROM teams WHERE id = $1", data.team_id)
-                .fetch_one(pool)
-                .await?;
+    AuditEntry {
+        action: "set_mod_permissions",
+        mod_name: Some(&data.mod_name),
+        team_id: Some(data.team_id),
+        target_id: Some(data.member),
+        details: Some(data.permissions.to_lowercase()),
+        ..Default::default()
+    }
+    .log(user_id, pool)
+    .await?;
 
-            let uri = req.uri();
-            dbg!(&uri);
-
-            if let Some(invite) = query.invite {
-                return Ok(HttpResponse::Ok().body(format!(
-                    "{}://{}:{}/public_api/teams/join/{}",
-                    match uri.scheme() {
-                        Some(x) => x.to_string(),
-                        None => "http".to_string(),
-                    },
-                    uri.host().unwrap_or("localhost"),
-                    uri.port_u16().unwrap_or(8000),
-                    invite
-                )));
-            } else {
-                let mut invite = utils::invite::create();
-
-                while sqlx::query!("SELECT * FROM teams WHERE invite = $1", &invite,)
-                    .fetch_optional(pool)
-                    .await?
-                    .is_some()
-                {
-                    invite = utils::invite::create();
+    Ok(HttpResponse::Ok().body("Successfully set the permissions of the member."))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PublicTeamMember {
+    id: String,
+    username: String,
+    avatar_url: Option<String>,
+    roles: i32,
+    /// `Owner`, `Admin`, `Mod` or `Member`.
+    role: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PublicTeamMod {
+    pub name: String,
+    /// The latest version that isn't yanked, or the latest one if they all are.
+    pub version: String,
+    pub description: String,
+    pub downloads: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PublicTeam {
+    id: i32,
+    name: String,
+    description: Option<String>,
+    avatar_url: Option<String>,
+    /// The members who let their profile and teams be seen.
+    members: Vec<PublicTeamMember>,
+    /// How many members are not listed.
+    hidden_members: usize,
+    mods: Vec<PublicTeamMod>,
+    /// Downloads of every version of every mod of the team.
+    downloads: i64,
+}
+
+/// A version of a mod, as needed by [`latest_public_mods`].
+pub struct ModVersionRow {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub downloads: i64,
+    pub yanked: bool,
+}
+
+/// Keeps the latest version of each mod, preferring the ones that aren't yanked, and sums the
+/// downloads of all of their versions. The most downloaded mods come first.
+pub fn latest_public_mods(versions: Vec<ModVersionRow>) -> Vec<PublicTeamMod> {
+    let mut mods: HashMap<String, (bool, Version, PublicTeamMod)> = HashMap::new();
+
+    for i in versions {
+        let version = if let Ok(x) = Version::parse(&i.version) {
+            x
+        } else {
+            continue;
+        };
+
+        let key = (!i.yanked, version.clone());
+
+        match mods.get_mut(&i.name) {
+            Some((yanked, latest, data)) => {
+                data.downloads += i.downloads;
+
+                if key > (!*yanked, latest.clone()) {
+                    *yanked = i.yanked;
+                    *latest = version;
+                    data.version = i.version;
+                    data.description = i.description;
                 }
+            }
+            None => {
+                mods.insert(
+                    i.name.to_string(),
+                    (
+                        i.yanked,
+                        version,
+                        PublicTeamMod {
+                            name: i.name,
+                            version: i.version,
+                            description: i.description,
+                            downloads: i.downloads,
+                        },
+                    ),
+                );
+            }
+        }
+    }
 
-                sqlx::query!(
-                    "UPDATE teams SET invite = $1 WHERE id = $2",
-                    &invite,
-                    data.team_id,
-                )
-                .execute(pool)
-                .await?;
+    let mut mods = mods.into_values().map(|i| i.2).collect::<Vec<_>>();
+    mods.sort_by_key(|i| std::cmp::Reverse(i.downloads));
+
+    mods
+}
+
+pub fn team_role_name(roles: TeamRoles) -> &'static str {
+    match roles.rank() {
+        3 => "Owner",
+        2 => "Admin",
+        1 => "Mod",
+        _ => "Member",
+    }
+}
 
-                return Ok(HttpResponse::Ok().body(format!(
-                    "{}://{}/teams/join/{}",
-                    uri.scheme().unwrap(),
-                    uri.authority().unwrap(),
-                    invite
-                )));
+/// Gets what's shown on the public page of a team, `None` if the team doesn't exist.
+async fn get_public_team(team_id: i32, pool: &PgPool) -> ServiceResult<Option<PublicTeam>> {
+    let query = sqlx::query!(
+        "SELECT id, name, description, avatar_url FROM teams WHERE id = $1",
+        team_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let team = if let Some(x) = query {
+        x
+    } else {
+        return Ok(None);
+    };
+
+    let query = sqlx::query!(
+        r#"SELECT m.member, m.roles, t.username, t.avatar_url, (t.profile_public AND t.show_teams) as "listed!" FROM team_members m JOIN tokens t ON t.owner_id = m.member WHERE m.team_id = $1"#,
+        team_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let hidden_members = query.iter().filter(|i| !i.listed).count();
+
+    let mut members = query
+        .into_iter()
+        .filter(|i| i.listed)
+        .map(|i| {
+            let id = i.member.to_string();
+
+            PublicTeamMember {
+                username: i.username.unwrap_or_else(|| id.to_string()),
+                id,
+                avatar_url: i.avatar_url,
+                roles: i.roles,
+                role: team_role_name(TeamRoles::from_bits_truncate(i.roles as u32)).to_string(),
             }
+        })
+        .collect::<Vec<_>>();
+
+    members
+        .sort_by_key(|i| std::cmp::Reverse(TeamRoles::from_bits_truncate(i.roles as u32).rank()));
+
+    let versions = sqlx::query_as!(
+        ModVersionRow,
+        r#"SELECT name, version, COALESCE(md.description, mods.description) as "description!", downloads, yanked FROM mods LEFT JOIN mod_metadata md ON md.mod_name = mods.name WHERE name IN (SELECT mod_name FROM owners WHERE owner_id = $1 AND is_team)"#,
+        team_id as i64,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mods = latest_public_mods(versions);
+
+    Ok(Some(PublicTeam {
+        downloads: mods.iter().map(|i| i.downloads).sum(),
+        id: team.id,
+        name: team.name,
+        description: team.description,
+        avatar_url: team.avatar_url,
+        members,
+        hidden_members,
+        mods,
+    }))
+}
+
+/// The public information of a team, its members and its mods.
+pub async fn public_team(
+    team_id: web::Path<i32>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    match get_public_team(*team_id, &db).await? {
+        Some(team) => Ok(HttpResponse::Ok().json(team)),
+        None => Ok(HttpResponse::NotFound().body("This team does not exist.")),
+    }
+}
+
+/// The public page of a team.
+pub async fn team_page(
+    team_id: web::Path<i32>,
+    hb: web::Data<Handlebars<'_>>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    match get_public_team(*team_id, &db).await? {
+        Some(team) => {
+            let body = hb.render("team", &team).unwrap();
+
+            Ok(HttpResponse::Ok().body(body))
         }
+        None => Ok(HttpResponse::NotFound().body("This team does not exist.")),
     }
+}
 
-    Err(ServiceError::Unauthorized)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditTeamData {
+    team_id: i32,
+    name: Option<String>,
+    /// Empty to remove it.
+    description: Option<String>,
+    /// An https url, empty to remove it.
+    avatar_url: Option<String>,
 }
 
-pub async fn join(
-    id: Identity,
-    data: web::Path<String>,
+/// Edits the name, description and avatar of a team, only its owner can.
+pub async fn edit_team(
+    principal: Principal,
+    data: web::Form<EditTeamData>,
     db: web::Data<PgPool>,
 ) -> ServiceResult<HttpResponse> {
     let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
 
-    if let Some(user_id) = id.identity() {
-        let user_id = user_id.parse::<i64>().unwrap();
+    match get_member_roles(data.team_id, user_id, pool).await? {
+        Some(roles) if roles.contains(TeamRoles::OWNER) => (),
+        _ => return Err(ServiceError::Unauthorized),
+    }
 
-        let query = sqlx::query!("SELECT name, id FROM teams WHERE invite = $1", &*data)
-            .fetch_optional(pool)
-            .await?;
+    let name = data.name.as_deref().map(str::trim);
 
-        if let Some(query) = query {
-            sqlx::query!(
-                "INSERT INTO team_members (team_id, member) VALUES ($1, $2)",
-                query.id,
-                user_id,
-            )
-            .execute(pool)
-            .await?;
+    if let Some(name) = name {
+        check_team_name(name, Some(data.team_id), pool).await?;
+    }
+
+    let description = data.description.as_deref().map(str::trim);
 
-            return Ok(HttpResponse::Ok().body(format!(
-                "Successfully joined team {} with id {}",
-                query.name, query.id
-            )));
+    if description
+        .map(|i| i.chars().count() > 1024)
+        .unwrap_or(false)
+    {
+        return Ok(HttpResponse::BadRequest()
+            .body("The description can't be longer than 1024 characters."));
+    }
+
+    let avatar_url = data.avatar_url.as_deref().map(str::trim);
+
+    if let Some(url) = avatar_url.filter(|i| !i.is_empty()) {
+        if !url.starts_with("https://") || url.len() > 512 {
+            return Ok(HttpResponse::BadRequest()
+                .body("The avatar must be an https url of up to 512 characters."));
         }
     }
 
-    Err(ServiceError::Unauthorized)
+    // Missing fields are left as they are, empty ones are removed.
+    sqlx::query!(
+        "UPDATE teams SET name = COALESCE($2, name), description = CASE WHEN $3::TEXT IS NULL THEN description ELSE NULLIF($3, '') END, avatar_url = CASE WHEN $4::TEXT IS NULL THEN avatar_url ELSE NULLIF($4, '') END WHERE id = $1",
+        data.team_id,
+        name,
+        description,
+        avatar_url,
+    )
+    .execute(pool)
+    .await?;
+
+    AuditEntry {
+        action: "edit_team",
+        team_id: Some(data.team_id),
+        details: name.map(|i| format!("Renamed to {}", i)),
+        ..Default::default()
+    }
+    .log(user_id, pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().body("Successfully edited the team."))
 }
@@ -0,0 +1,311 @@
+use crate::auth::Principal;
+use crate::error::*;
+use crate::model::*;
+use crate::utils::tokens::{create_token, log_token_event, request_ip, rotate_token};
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TokenInfo {
+    id: i32,
+    name: String,
+    scopes: Vec<String>,
+    expires: Option<String>,
+    last_used: Option<String>,
+    created: String,
+}
+
+/// Gets the tokens of an account, without their hashes.
+async fn get_tokens(owner_id: i64, pool: &PgPool) -> ServiceResult<Vec<TokenInfo>> {
+    let tokens = sqlx::query!(
+        "SELECT id, name, scopes, expires, last_used, created FROM api_tokens WHERE owner_id = $1 ORDER BY created",
+        owner_id,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| TokenInfo {
+        id: i.id,
+        name: i.name,
+        scopes: i.scopes,
+        expires: i.expires.map(|x| x.to_rfc3339()),
+        last_used: i.last_used.map(|x| x.to_rfc3339()),
+        created: i.created.to_rfc3339(),
+    })
+    .collect();
+
+    Ok(tokens)
+}
+
+/// Deletes a token of an account, returns false if the account has no token with that id.
+async fn delete_token(
+    owner_id: i64,
+    token_id: i32,
+    ip: Option<&str>,
+    pool: &PgPool,
+) -> ServiceResult<bool> {
+    let query = sqlx::query!(
+        "DELETE FROM api_tokens WHERE id = $1 AND owner_id = $2 RETURNING name",
+        token_id,
+        owner_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(data) = query {
+        log_token_event(owner_id, token_id, &data.name, "revoked", ip, pool).await?;
+
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TokenEvent {
+    token_id: Option<i32>,
+    token_name: String,
+    event: String,
+    ip: Option<String>,
+    created: String,
+}
+
+/// Gets the latest 100 entries of the security log of an account.
+async fn get_token_events(owner_id: i64, pool: &PgPool) -> ServiceResult<Vec<TokenEvent>> {
+    let events = sqlx::query!(
+        "SELECT token_id, token_name, event, ip, created FROM token_events WHERE owner_id = $1 ORDER BY created DESC LIMIT 100",
+        owner_id,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|i| TokenEvent {
+        token_id: i.token_id,
+        token_name: i.token_name,
+        event: i.event,
+        ip: i.ip,
+        created: i.created.to_rfc3339(),
+    })
+    .collect();
+
+    Ok(events)
+}
+
+/// Lists the tokens of the principal, requires the `read` scope.
+pub async fn list(principal: Principal, db: web::Data<PgPool>) -> ServiceResult<HttpResponse> {
+    principal.require_scope(Scope::Read)?;
+
+    Ok(HttpResponse::Ok().json(get_tokens(principal.id, db.as_ref()).await?))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTokenData {
+    name: String,
+    /// Comma separated list of scopes.
+    scopes: String,
+    /// Days until the token expires, it never expires when missing.
+    expires_in: Option<i32>,
+}
+
+/// Creates a new token for the logged in user, the token is only shown in this response.
+pub async fn create(
+    req: HttpRequest,
+    principal: Principal,
+    data: web::Form<CreateTokenData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    let name = data.name.trim();
+
+    if name.is_empty() || name.len() > 64 {
+        return Ok(HttpResponse::BadRequest().body("The name must be between 1 and 64 characters."));
+    }
+
+    let mut scopes = Vec::new();
+
+    for i in data
+        .scopes
+        .split(',')
+        .map(str::trim)
+        .filter(|i| !i.is_empty())
+    {
+        match i.parse::<Scope>() {
+            Ok(scope) if !scopes.contains(&scope) => scopes.push(scope),
+            Ok(_) => (),
+            Err(why) => return Ok(HttpResponse::BadRequest().body(why)),
+        }
+    }
+
+    if scopes.is_empty() {
+        return Ok(HttpResponse::BadRequest().body("At least one scope is required."));
+    }
+
+    if let Some(days) = data.expires_in {
+        if !(1..=365).contains(&days) {
+            return Ok(
+                HttpResponse::BadRequest().body("Tokens can expire in between 1 and 365 days.")
+            );
+        }
+    }
+
+    let query = sqlx::query!(
+        "SELECT id FROM api_tokens WHERE owner_id = $1 AND name = $2",
+        user_id,
+        name,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.is_some() {
+        return Ok(HttpResponse::BadRequest().body("A token with the same name already exists."));
+    }
+
+    let token = create_token(
+        user_id,
+        name,
+        &scopes,
+        data.expires_in,
+        request_ip(&req).as_deref(),
+        pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().body(token))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeTokenData {
+    id: i32,
+}
+
+/// Revokes a token of the principal.
+///
+/// No scope is required, so any token can revoke itself or the other tokens of its owner.
+pub async fn revoke(
+    req: HttpRequest,
+    principal: Principal,
+    data: web::Form<RevokeTokenData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+
+    if delete_token(principal.id, data.id, request_ip(&req).as_deref(), pool).await? {
+        Ok(HttpResponse::Ok().body("Successfully revoked the token."))
+    } else {
+        Ok(HttpResponse::BadRequest().body("This token does not exist."))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateTokenData {
+    id: i32,
+    /// Hours the old token keeps working for, it stops working immediately when missing.
+    grace_period: Option<i32>,
+}
+
+/// Checks the grace period of a rotation, returning the response for an invalid one.
+pub fn check_grace_period(grace_period: Option<i32>) -> Option<HttpResponse> {
+    match grace_period {
+        Some(hours) if !(0..=168).contains(&hours) => Some(
+            HttpResponse::BadRequest().body("The grace period must be between 0 and 168 hours."),
+        ),
+        _ => None,
+    }
+}
+
+/// Replaces a token of the logged in user, the new token is only shown in this response.
+pub async fn rotate(
+    req: HttpRequest,
+    principal: Principal,
+    data: web::Form<RotateTokenData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_session()?;
+
+    if let Some(resp) = check_grace_period(data.grace_period) {
+        return Ok(resp);
+    }
+
+    let token = rotate_token(
+        principal.id,
+        data.id,
+        data.grace_period.unwrap_or(0),
+        request_ip(&req).as_deref(),
+        pool,
+    )
+    .await?;
+
+    if let Some(token) = token {
+        Ok(HttpResponse::Ok().body(token))
+    } else {
+        Ok(HttpResponse::BadRequest().body("This token does not exist."))
+    }
+}
+
+/// Shows the security log of the principal, requires the `read` scope.
+pub async fn events(principal: Principal, db: web::Data<PgPool>) -> ServiceResult<HttpResponse> {
+    principal.require_scope(Scope::Read)?;
+
+    Ok(HttpResponse::Ok().json(get_token_events(principal.id, db.as_ref()).await?))
+}
+
+/// Same as `revoke`, with the id in the query string.
+pub async fn api_revoke(
+    req: HttpRequest,
+    principal: Principal,
+    data: web::Query<RevokeTokenData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    principal.require_write()?;
+
+    if delete_token(principal.id, data.id, request_ip(&req).as_deref(), pool).await? {
+        Ok(HttpResponse::Ok().body("Successfully revoked the token."))
+    } else {
+        Ok(HttpResponse::BadRequest().body("This token does not exist."))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiRotateTokenData {
+    grace_period: Option<i32>,
+}
+
+/// Replaces the token provided, keeping its name, scopes and expiry.
+///
+/// Only the token used for the request can be rotated, as the new token would otherwise give
+/// access to scopes the caller might not have.
+pub async fn api_rotate(
+    req: HttpRequest,
+    principal: Principal,
+    data: web::Query<ApiRotateTokenData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+    let token_id = principal
+        .token_id
+        .ok_or_else(|| ServiceError::BadRequest("No token was used for this request.".into()))?;
+
+    if let Some(resp) = check_grace_period(data.grace_period) {
+        return Ok(resp);
+    }
+
+    let new_token = rotate_token(
+        principal.id,
+        token_id,
+        data.grace_period.unwrap_or(0),
+        request_ip(&req).as_deref(),
+        pool,
+    )
+    .await?;
+
+    if let Some(new_token) = new_token {
+        Ok(HttpResponse::Ok().body(new_token))
+    } else {
+        Ok(HttpResponse::BadRequest().body("This token does not exist."))
+    }
+}
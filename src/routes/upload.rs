@@ -1,8 +1,14 @@
+use crate::auth::Principal;
 use crate::error::ServiceResult;
-use crate::model::Config;
+use crate::model::{Config, ModPermissions, Roles};
+use crate::routes::deprecation::get_deprecated_dependencies;
+use crate::utils::archive::hash_files;
+use crate::utils::audit::AuditEntry;
+use crate::utils::names::{check_new_name, normalize};
+use crate::utils::ownership::mod_permissions;
 
 use actix_multipart::Multipart;
-use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::{web, HttpResponse};
 
 use futures::stream::{self, StreamExt, TryStreamExt};
 use rand::Rng;
@@ -96,7 +102,7 @@ pub struct ModJsonData {
 
 /// curl -X POST http://localhost:8000/api/upload -i -H 'Authorization: asdasdasd' --form "mod=@mod.zip" --form "data=@data.json"
 pub async fn upload(
-    req: HttpRequest,
+    user: Principal,
     config: web::Data<Config>,
     db: web::Data<PgPool>,
     mut payload: Multipart,
@@ -254,38 +260,93 @@ pub async fn upload(
         );
     }
 
-    let dependencies_checksums = dependencies_data
+    let archive_path = filepath.to_string();
+
+    let file_hashes = match web::block(move || hash_files(&archive_path)).await {
+        Ok(x) => x,
+        Err(why) => {
+            if let Err(why) = tokio::fs::remove_file(&filepath).await {
+                error!(
+                    "Could not delete file `{}` due to a failed upload.\n{:#?}",
+                    &mod_checksum_path, why
+                );
+            };
+
+            return Ok(HttpResponse::BadRequest().body(format!("Invalid zip file: {}", why)));
+        }
+    };
+
+    let mut hashes = file_hashes
         .iter()
-        .map(|i| i.checksum.to_string())
+        .map(|i| i.1.to_string())
         .collect::<Vec<String>>();
+    hashes.push(checksum.to_string());
 
-    let user = sqlx::query!(
-        "SELECT owner_id, roles, is_team FROM tokens WHERE token = $1",
-        req.headers()
-            .get("Authorization")
-            // unwrap is safe this method only runs when the /api token check has been done.
-            .unwrap()
-            .to_str()
-            .unwrap()
-    )
-    .fetch_one(pool)
-    .await?;
-
-    let owner = sqlx::query!(
-        "SELECT mod_name FROM owners WHERE owner_id = $1 AND mod_name = $2",
-        user.owner_id,
-        &data.name,
+    let blocked = sqlx::query!(
+        "SELECT hash, reason FROM blocklist WHERE hash = ANY($1) LIMIT 1",
+        &hashes,
     )
     .fetch_optional(pool)
     .await?;
 
+    if let Some(blocked) = blocked {
+        warn!(
+            "Refused upload of `{}` containing the blocklisted hash `{}`",
+            &data.name, &blocked.hash
+        );
+
+        if let Err(why) = tokio::fs::remove_file(&filepath).await {
+            error!(
+                "Could not delete file `{}` due to a failed upload.\n{:#?}",
+                &mod_checksum_path, why
+            );
+        };
+
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "This mod contains a blocklisted file: {}",
+            blocked.reason
+        )));
+    }
+
+    let dependencies_checksums = dependencies_data
+        .iter()
+        .map(|i| i.checksum.to_string())
+        .collect::<Vec<String>>();
+
+    if !user.can_publish(&data.name) {
+        if let Err(why) = tokio::fs::remove_file(&filepath).await {
+            error!(
+                "Could not delete file `{}` due to a failed upload.\n{:#?}",
+                &mod_checksum_path, why
+            );
+        };
+
+        return Ok(HttpResponse::Forbidden().body(format!(
+            "This token is missing the `publish` or `publish:{}` scope.",
+            data.name
+        )));
+    }
+
+    // Members of the team that owns the mod can publish with their own tokens.
+    let permissions = mod_permissions(user.id, &data.name, pool).await?;
+
     let mut transaction = pool.begin().await?;
 
-    if owner.is_some() {
+    if let Some(permissions) = permissions {
+        if !permissions.contains(ModPermissions::PUBLISH) {
+            if let Err(why) = tokio::fs::remove_file(&filepath).await {
+                error!(
+                    "Could not delete file `{}` due to a failed upload.\n{:#?}",
+                    &mod_checksum_path, why
+                );
+            };
+
+            return Ok(HttpResponse::Unauthorized().body("You can't publish this mod"));
+        }
+
         sqlx::query!(
-            "UPDATE owners SET checksums = array_append(checksums::text[], $1) WHERE owner_id = $2 AND mod_name = $3",
+            "UPDATE owners SET checksums = array_append(checksums::text[], $1) WHERE mod_name = $2",
             &checksum,
-            user.owner_id,
             &data.name,
         )
         .execute(&mut transaction)
@@ -305,15 +366,55 @@ pub async fn upload(
 
             return Ok(HttpResponse::Unauthorized().body("You do not own this mod"));
         } else {
+            let can_use_reserved = user.roles.intersects(Roles::ADMIN | Roles::OWNER);
+            let mut refused = check_new_name(&data.name, can_use_reserved, pool).await?;
+
+            if refused.is_none() {
+                // Two mods could be registered with a similar name at the same time.
+                let query = sqlx::query!(
+                    "INSERT INTO mod_names (normalized, name, skeleton) VALUES ($1, $2, mod_name_skeleton($2)) ON CONFLICT DO NOTHING RETURNING normalized",
+                    normalize(&data.name),
+                    &data.name,
+                )
+                .fetch_optional(&mut transaction)
+                .await?;
+
+                if query.is_none() {
+                    refused = Some(
+                        "This name, or one too similar to it, has just been taken.".to_string(),
+                    );
+                }
+            }
+
+            if let Some(why) = refused {
+                if let Err(why) = tokio::fs::remove_file(&filepath).await {
+                    error!(
+                        "Could not delete file `{}` due to a failed upload.\n{:#?}",
+                        &mod_checksum_path, why
+                    );
+                };
+
+                return Ok(HttpResponse::BadRequest().body(why));
+            }
+
             sqlx::query!(
                 "INSERT INTO owners (owner_id, mod_name, checksums, is_team) VALUES ($1, $2, $3, $4)",
-                user.owner_id,
+                user.id,
                 &data.name,
                 &vec![checksum.to_string()],
                 user.is_team,
             )
             .execute(&mut transaction)
             .await?;
+
+            AuditEntry {
+                action: "publish_new_mod",
+                mod_name: Some(&data.name),
+                checksum: Some(&checksum),
+                ..Default::default()
+            }
+            .log(user.id, &mut transaction)
+            .await?;
         }
     }
 
@@ -322,9 +423,9 @@ pub async fn upload(
 
     let query = sqlx::query!(
         "INSERT INTO mods
-        (name, version, description, repository_git, repository_hg, authors, documentation, readme, readme_filename, license, license_filename, homepage, keywords, build_script, dependencies_checksums, metadata, checksum)
+        (name, version, description, repository_git, repository_hg, authors, documentation, readme, readme_filename, license, license_filename, homepage, keywords, build_script, dependencies_checksums, metadata, checksum, uploaded_by)
         VALUES
-        ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)",
+        ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)",
         &data.name,
         &data.version,
         &data.description,
@@ -342,6 +443,7 @@ pub async fn upload(
         &dependencies_checksums,
         &data.metadata,
         &checksum,
+        user.id,
     )
     .execute(&mut transaction)
     .await;
@@ -357,9 +459,59 @@ pub async fn upload(
         return Ok(HttpResponse::BadRequest().body(&format!("Database error: {}", why)));
     }
 
+    // The fields the new version sets replace the edits of the owners, the other edits are kept.
+    sqlx::query!(
+        r#"
+            UPDATE
+                mod_metadata
+            SET
+                description = NULL,
+                homepage = CASE WHEN $2 THEN NULL ELSE homepage END,
+                documentation = CASE WHEN $3 THEN NULL ELSE documentation END,
+                keywords = CASE WHEN $4 THEN NULL ELSE keywords END,
+                repository_git = CASE WHEN $5 THEN NULL ELSE repository_git END,
+                repository_hg = CASE WHEN $6 THEN NULL ELSE repository_hg END
+            WHERE
+                mod_name = $1
+        "#,
+        &data.name,
+        data.homepage.is_some(),
+        data.documentation.is_some(),
+        !data.keywords.is_empty(),
+        data.repository_git.is_some(),
+        data.repository_hg.is_some(),
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM mod_metadata WHERE mod_name = $1 AND homepage IS NULL AND documentation IS NULL AND keywords IS NULL AND repository_git IS NULL AND repository_hg IS NULL",
+        &data.name,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let (filenames, file_hashes): (Vec<String>, Vec<String>) = file_hashes.into_iter().unzip();
+
+    sqlx::query!(
+        "INSERT INTO mod_files (checksum, filename, hash) SELECT $1, * FROM UNNEST($2::text[], $3::text[]) ON CONFLICT DO NOTHING",
+        &checksum,
+        &filenames,
+        &file_hashes,
+    )
+    .execute(&mut transaction)
+    .await?;
+
     tokio::fs::rename(&filepath, &mod_checksum_path).await?;
 
     transaction.commit().await?;
 
-    Ok("ok".into())
+    // Deprecated dependencies don't stop the upload, but the uploader is warned about them.
+    let warnings = get_deprecated_dependencies(&dependencies_checksums, pool)
+        .await?
+        .into_iter()
+        .map(|i| format!("\nWarning: {}", i.deprecation.warning(&i.name)))
+        .collect::<String>();
+
+    Ok(HttpResponse::Ok().body(format!("ok{}", warnings)))
 }
@@ -1,18 +1,20 @@
+use crate::auth::Principal;
 use crate::error::*;
 use crate::model::*;
-use crate::routes::search::{QueryData, SearchModsResponse};
-
-use actix_identity::Identity;
+use crate::providers::Providers;
+use crate::routes::advisories::get_advisories;
+use crate::routes::deprecation::get_deprecations;
+use crate::routes::search::SearchModsResponse;
+use crate::routes::teams::{latest_public_mods, team_role_name, ModVersionRow, PublicTeamMod};
+use crate::utils::profiles::{get_profile, Profile};
+
+use actix_identity::RequestIdentity;
 use actix_web::http::header;
 use actix_web::{web, HttpRequest, HttpResponse};
 
-use darkredis::ConnectionPool;
-use futures::StreamExt;
 use handlebars::Handlebars;
-use semver::Version;
 use sqlx::postgres::PgPool;
 
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TeamInfo {
     name: String,
@@ -20,54 +22,57 @@ pub struct TeamInfo {
     roles: i32,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MeModData {
+    #[serde(flatten)]
+    data: SearchModsResponse,
+    /// The team the mod is owned through, `None` if the user owns it directly.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    team_id: Option<i32>,
+}
+
+/// What the user shows to others on their public profile.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PrivacySettings {
+    /// When false the public profile of the user can only be seen by themselves.
+    profile_public: bool,
+    /// When false the teams of the user, and the mods they own through them, are left out of
+    /// their public profile.
+    show_teams: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MeResponseData {
     roles: u32,
     user_id: u64,
     user_id_string: String,
-    is_banned: bool,
-    token: String,
 
-    discord: UserResponse,
-    mods: Vec<SearchModsResponse>,
+    profile: Profile,
+    mods: Vec<MeModData>,
     teams: Vec<TeamInfo>,
-}
-
-pub async fn get_user_data(token: &str) -> ServiceResult<UserResponse> {
-    let client = reqwest::Client::new();
-
-    let user = client
-        .get(&format!("{}/users/@me", API_ENDPOINT))
-        .bearer_auth(&token)
-        .send()
-        .await?
-        .json::<UserResponse>()
-        .await?;
-
-    Ok(user)
+    /// Only sent to verifiers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pending_verifications: Option<i64>,
+    privacy: PrivacySettings,
 }
 
 pub async fn index(
-    id: Identity,
+    principal: Option<Principal>,
     hb: web::Data<Handlebars<'_>>,
-    redis: web::Data<ConnectionPool>,
+    db: web::Data<PgPool>,
+    providers: web::Data<Providers>,
 ) -> ServiceResult<HttpResponse> {
-    let mut conn = redis.get().await;
-
-    if let Some(user_id) = id.identity() {
-        if let Ok(Some(token)) = conn.get(&user_id).await {
-            let token = String::from_utf8(token).unwrap();
-            let user = get_user_data(&token).await?;
+    if let Some(user_id) = principal.and_then(|i| i.user_id().ok()) {
+        let user = get_profile(user_id, &providers, &db).await?;
 
-            let data = serde_json::json!({
-                "name": user.username,
-                "discriminator": user.discriminator,
-            });
+        let data = serde_json::json!({
+            "name": user.username,
+            "discriminator": user.discriminator,
+        });
 
-            let body = hb.render("discord_user", &data).unwrap();
+        let body = hb.render("user", &data).unwrap();
 
-            return Ok(HttpResponse::Ok().body(body));
-        }
+        return Ok(HttpResponse::Ok().body(body));
     }
 
     Ok(HttpResponse::Found()
@@ -75,265 +80,385 @@ pub async fn index(
         .finish())
 }
 
+/// Gets the latest version of every mod the user owns, directly or through one of their teams.
+///
+/// Versions are compared by their numeric parts, releases before pre-releases, and pre-releases of
+/// the same version by upload date.
+async fn get_owned_mods(user_id: i64, pool: &PgPool) -> ServiceResult<Vec<MeModData>> {
+    let query = sqlx::query!(
+        r#"
+            WITH owned AS (
+                SELECT DISTINCT ON (mod_name)
+                    mod_name,
+                    CASE WHEN is_team THEN owner_id::INT END as team_id
+                FROM
+                    owners
+                WHERE
+                    (owner_id = $1 AND NOT is_team)
+                    OR (
+                        is_team
+                        AND owner_id IN (SELECT team_id::BIGINT FROM team_members WHERE member = $1)
+                    )
+                ORDER BY
+                    mod_name,
+                    is_team
+            )
+            SELECT DISTINCT ON (m.name)
+                m.checksum,
+                m.name,
+                m.version,
+                COALESCE(md.description, m.description) as "description!",
+                COALESCE(md.keywords, m.keywords) as keywords,
+                m.verification as "verification: Verification",
+                m.downloads,
+                m.uploaded,
+                m.yanked,
+                o.team_id
+            FROM
+                mods m
+                JOIN owned o ON o.mod_name = m.name
+                LEFT JOIN mod_metadata md ON md.mod_name = m.name
+            ORDER BY
+                m.name,
+                string_to_array(regexp_replace(m.version, '[-+].*$', ''), '.')::NUMERIC[] DESC,
+                m.version !~ '^[0-9.]+-' DESC,
+                m.uploaded DESC
+        "#,
+        user_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let names = query.iter().map(|i| i.name.to_string()).collect::<Vec<_>>();
+    let mut advisories = get_advisories(&names, pool).await?;
+    let mut deprecations = get_deprecations(&names, pool).await?;
+
+    Ok(query
+        .into_iter()
+        .map(|i| {
+            let advisories = advisories
+                .remove(&i.name)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|a| a.affects(&i.version))
+                .collect();
+            let deprecated = deprecations.remove(&i.name);
+
+            MeModData {
+                data: SearchModsResponse {
+                    checksum: i.checksum,
+                    name: i.name,
+                    version: i.version,
+                    description: i.description,
+                    keywords: i.keywords.unwrap_or_default(),
+                    verification: i.verification.unwrap_or_default(),
+                    downloads: i.downloads,
+                    uploaded: i.uploaded.to_rfc3339(),
+                    yanked: i.yanked,
+                    advisories,
+                    deprecated,
+                },
+                team_id: i.team_id,
+            }
+        })
+        .collect())
+}
+
+/// How many versions still need a manual verification that the verifier didn't vote on yet.
+async fn get_pending_verifications(verifier_id: i64, pool: &PgPool) -> ServiceResult<i64> {
+    let query = sqlx::query!(
+        r#"
+            SELECT
+                COUNT(*) as "count!"
+            FROM
+                mods
+            WHERE
+                NOT yanked
+                AND (verification IS NULL OR verification = 'Auto')
+                AND checksum NOT IN (SELECT checksum FROM verification WHERE verifier_id = $1)
+        "#,
+        verifier_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(query.count)
+}
+
+/// The profile of the principal, nothing when the request has no credentials.
+///
+/// Invalid or expired credentials are refused, and so are banned accounts.
 pub async fn me(
     req: HttpRequest,
-    id: Identity,
-    redis: web::Data<ConnectionPool>,
+    principal: Result<Principal, ServiceError>,
     db: web::Data<PgPool>,
+    providers: web::Data<Providers>,
 ) -> ServiceResult<HttpResponse> {
     let pool = &**db;
-    let mut conn = redis.get().await;
-    let mut resp_data = None;
-
-    if let Some(user_id) = id.identity() {
-        if let Ok(Some(token)) = conn.get(&user_id).await {
-            let token = String::from_utf8(token).unwrap();
-            let user = get_user_data(&token).await?;
-
-            let query = sqlx::query!("SELECT * FROM tokens WHERE owner_id = $1", user.id as i64)
-                .fetch_optional(pool)
-                .await?;
-
-            if let Some(data) = query {
-                let roles = Roles::from_bits_truncate(data.roles as u32);
-
-                let mut mods = vec![];
-
-                let query = sqlx::query!("SELECT * FROM owners WHERE owner_id = $1", data.owner_id)
-                    .fetch_all(pool)
-                    .await?;
-
-                for i in query {
-                    let mut query = sqlx::query_as!(
-                        QueryData,
-                        r#"
-                            SELECT
-                                checksum,
-                                name,
-                                version,
-                                description,
-                                keywords,
-                                verification as "verification: Verification",
-                                downloads,
-                                uploaded
-                            FROM
-                                mods
-                            WHERE
-                                name = $1
-                            ORDER BY
-                                uploaded
-                                ASC
-                        "#,
-                        &i.mod_name
-                    )
-                    .fetch(pool)
-                    .boxed();
-
-                    let mut le_mod: Option<SearchModsResponse> = None;
-
-                    while let Some(Ok(value)) = query.next().await {
-                        if let Some(ref m) = le_mod {
-                            let ver = Version::parse(&value.version).unwrap();
-                            let m = Version::parse(&m.version).unwrap();
-
-                            if ver > m {
-                                le_mod = Some(SearchModsResponse {
-                                    checksum: value.checksum.to_string(),
-                                    name: value.name.to_string(),
-                                    version: value.version.to_string(),
-                                    description: value.description.to_string(),
-                                    keywords: value.keywords.clone().unwrap_or_default(),
-                                    verification: value.verification.clone().unwrap_or_default(),
-                                    downloads: value.downloads,
-                                    uploaded: value.uploaded.to_rfc3339(),
-                                });
-                            }
-                        } else {
-                            le_mod = Some(SearchModsResponse {
-                                checksum: value.checksum.to_string(),
-                                name: value.name.to_string(),
-                                version: value.version.to_string(),
-                                description: value.description.to_string(),
-                                keywords: value.keywords.clone().unwrap_or_default(),
-                                verification: value.verification.clone().unwrap_or_default(),
-                                downloads: value.downloads,
-                                uploaded: value.uploaded.to_rfc3339(),
-                            });
-                        }
-                    }
-
-                    if let Some(ref m) = le_mod {
-                        mods.push(m.clone());
-                    }
-                }
-
-                let mut teams = Vec::new();
-
-                let query = sqlx::query!(
-                    "SELECT * FROM team_members WHERE member = $1",
-                    data.owner_id
-                )
-                .fetch_all(pool)
-                .await?;
 
-                for i in query {
-                    let team = sqlx::query!(
-                        "SELECT name FROM teams WHERE id = $1",
-                        i.team_id,
-                    )
-                    .fetch_one(pool)
-                    .await?;
-
-                    teams.push(TeamInfo {
-                        name: team.name,
-                        id: i.team_id,
-                        roles: i.roles,
-                    });
-                }
-
-                resp_data = Some(MeResponseData {
-                    roles: roles.bits(),
-                    user_id: data.owner_id as u64,
-                    user_id_string: data.owner_id.to_string(),
-                    is_banned: data.is_banned,
-                    token: token.to_string(),
-                    discord: user,
-                    mods,
-                    teams,
-                });
-            } else {
-                return Ok(HttpResponse::NoContent().finish());
-            }
+    let principal = match principal {
+        Ok(x) => x,
+        Err(_) if req.headers().get("Authorization").is_none() && req.get_identity().is_none() => {
+            return Ok(HttpResponse::NoContent().finish());
         }
-    }
+        Err(why) => return Err(why),
+    };
 
-    if let Some(token) = req.headers().get("Authorization") {
-        let token = token.to_str().unwrap();
-
-        let query = sqlx::query!("SELECT * FROM tokens WHERE token = $1", token)
-            .fetch_optional(pool)
-            .await?;
-
-        if let Some(data) = query {
-            if let Ok(Some(oauth_token)) = conn.get(&data.owner_id.to_string()).await {
-                let user = get_user_data(&String::from_utf8(oauth_token).unwrap()).await?;
-                let roles = Roles::from_bits_truncate(data.roles as u32);
-
-                let mut mods = vec![];
-
-                let query = sqlx::query!("SELECT * FROM owners WHERE owner_id = $1", data.owner_id)
-                    .fetch_all(pool)
-                    .await?;
-
-                for i in query {
-                    let mut query = sqlx::query_as!(
-                        QueryData,
-                        r#"
-                            SELECT
-                                checksum,
-                                name,
-                                version,
-                                description,
-                                keywords,
-                                verification as "verification: Verification",
-                                downloads,
-                                uploaded
-                            FROM
-                                mods
-                            WHERE
-                                name = $1
-                            ORDER BY
-                                uploaded
-                                ASC
-                        "#,
-                        &i.mod_name
-                    )
-                    .fetch(pool)
-                    .boxed();
-
-                    let mut le_mod: Option<SearchModsResponse> = None;
-
-                    while let Some(Ok(value)) = query.next().await {
-                        if let Some(ref m) = le_mod {
-                            let ver = Version::parse(&value.version).unwrap();
-                            let m = Version::parse(&m.version).unwrap();
-
-                            if ver > m {
-                                le_mod = Some(SearchModsResponse {
-                                    checksum: value.checksum.to_string(),
-                                    name: value.name.to_string(),
-                                    version: value.version.to_string(),
-                                    description: value.description.to_string(),
-                                    keywords: value.keywords.clone().unwrap_or_default(),
-                                    verification: value.verification.clone().unwrap_or_default(),
-                                    downloads: value.downloads,
-                                    uploaded: value.uploaded.to_rfc3339(),
-                                });
-                            }
-                        } else {
-                            le_mod = Some(SearchModsResponse {
-                                checksum: value.checksum.to_string(),
-                                name: value.name.to_string(),
-                                version: value.version.to_string(),
-                                description: value.description.to_string(),
-                                keywords: value.keywords.clone().unwrap_or_default(),
-                                verification: value.verification.clone().unwrap_or_default(),
-                                downloads: value.downloads,
-                                uploaded: value.uploaded.to_rfc3339(),
-                            });
-                        }
-                    }
-
-                    if let Some(ref m) = le_mod {
-                        mods.push(m.clone());
-                    }
-                }
-
-                let mut teams = Vec::new();
-
-                let query = sqlx::query!(
-                    "SELECT * FROM team_members WHERE member = $1",
-                    data.owner_id
+    principal.require_scope(Scope::Read)?;
+    let user_id = principal.user_id()?;
+
+    let user = get_profile(user_id, &providers, pool).await?;
+    let mods = get_owned_mods(user_id, pool).await?;
+
+    let teams = sqlx::query_as!(
+        TeamInfo,
+        "SELECT t.name, m.team_id as id, m.roles FROM team_members m JOIN teams t ON t.id = m.team_id WHERE m.member = $1 ORDER BY t.name",
+        user_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let pending_verifications = if principal.roles.contains(Roles::VERIFIER) {
+        Some(get_pending_verifications(user_id, pool).await?)
+    } else {
+        None
+    };
+
+    let privacy = sqlx::query_as!(
+        PrivacySettings,
+        "SELECT profile_public, show_teams FROM tokens WHERE owner_id = $1",
+        user_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(MeResponseData {
+        roles: principal.roles.bits(),
+        user_id: user_id as u64,
+        user_id_string: user_id.to_string(),
+        profile: user,
+        mods,
+        teams,
+        pending_verifications,
+        privacy,
+    }))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PublicUserTeam {
+    id: i32,
+    name: String,
+    /// `Owner`, `Admin`, `Mod` or `Member`.
+    role: String,
+}
+
+/// The public profile of a user.
+///
+/// Only holds what the user already shows on their mods and teams, never their email, linked
+/// accounts or Discord tag.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PublicUser {
+    id: String,
+    username: String,
+    avatar_url: Option<String>,
+    /// The names of the roles of the user on the site, e.g. `Verifier`.
+    badges: Vec<String>,
+    /// Empty if the user hides their teams.
+    teams: Vec<PublicUserTeam>,
+    /// The mods the user owns, directly or through their teams.
+    mods: Vec<PublicTeamMod>,
+    /// Downloads of every version of every mod in `mods`.
+    downloads: i64,
+    /// How many versions the user uploaded to the mods in `mods`.
+    versions_published: i64,
+}
+
+fn role_badges(roles: Roles) -> Vec<String> {
+    [
+        (Roles::OWNER, "Owner"),
+        (Roles::ADMIN, "Admin"),
+        (Roles::MOD, "Mod"),
+        (Roles::VERIFIER, "Verifier"),
+        (Roles::MAPPER, "Mapper"),
+        (Roles::BOT, "Bot"),
+    ]
+    .iter()
+    .filter(|(role, _)| roles.contains(*role))
+    .map(|(_, name)| name.to_string())
+    .collect()
+}
+
+/// Gets what's shown on the public profile of a user.
+///
+/// `None` if the user doesn't exist, is a team, or made their profile private and isn't the one
+/// looking at it.
+async fn get_public_user(
+    user_id: i64,
+    viewer: Option<i64>,
+    pool: &PgPool,
+) -> ServiceResult<Option<PublicUser>> {
+    let query = sqlx::query!(
+        "SELECT username, avatar_url, roles, profile_public, show_teams FROM tokens WHERE owner_id = $1 AND NOT is_team",
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let user = match query {
+        Some(x) if x.profile_public || viewer == Some(user_id) => x,
+        _ => return Ok(None),
+    };
+
+    let teams = if user.show_teams {
+        sqlx::query!(
+            "SELECT t.id, t.name, m.roles FROM team_members m JOIN teams t ON t.id = m.team_id WHERE m.member = $1 ORDER BY t.name",
+            user_id,
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|i| PublicUserTeam {
+            id: i.id,
+            name: i.name,
+            role: team_role_name(TeamRoles::from_bits_truncate(i.roles as u32)).to_string(),
+        })
+        .collect()
+    } else {
+        Vec::new()
+    };
+
+    let versions = sqlx::query_as!(
+        ModVersionRow,
+        r#"
+            SELECT
+                name,
+                version,
+                COALESCE(md.description, mods.description) as "description!",
+                downloads,
+                yanked
+            FROM
+                mods
+                LEFT JOIN mod_metadata md ON md.mod_name = mods.name
+            WHERE
+                name IN (
+                    SELECT
+                        mod_name
+                    FROM
+                        owners
+                    WHERE
+                        (owner_id = $1 AND NOT is_team)
+                        OR (
+                            $2
+                            AND is_team
+                            AND owner_id IN (SELECT team_id::BIGINT FROM team_members WHERE member = $1)
+                        )
+                )
+        "#,
+        user_id,
+        user.show_teams,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mods = latest_public_mods(versions);
+
+    let versions_published = sqlx::query!(
+        r#"
+            SELECT
+                COUNT(*) as "count!"
+            FROM
+                mods
+            WHERE
+                uploaded_by = $1
+                AND name IN (
+                    SELECT
+                        mod_name
+                    FROM
+                        owners
+                    WHERE
+                        (owner_id = $1 AND NOT is_team)
+                        OR (
+                            $2
+                            AND is_team
+                            AND owner_id IN (SELECT team_id::BIGINT FROM team_members WHERE member = $1)
+                        )
                 )
-                .fetch_all(pool)
-                .await?;
+        "#,
+        user_id,
+        user.show_teams,
+    )
+    .fetch_one(pool)
+    .await?
+    .count;
+
+    Ok(Some(PublicUser {
+        id: user_id.to_string(),
+        username: user.username.unwrap_or_else(|| user_id.to_string()),
+        avatar_url: user.avatar_url,
+        badges: role_badges(Roles::from_bits_truncate(user.roles as u32)),
+        teams,
+        downloads: mods.iter().map(|i| i.downloads).sum(),
+        mods,
+        versions_published,
+    }))
+}
 
-                for i in query {
-                    let team = sqlx::query!(
-                        "SELECT name FROM teams WHERE id = $1",
-                        i.team_id,
-                    )
-                    .fetch_one(pool)
-                    .await?;
-
-                    teams.push(TeamInfo {
-                        name: team.name,
-                        id: i.team_id,
-                        roles: i.roles,
-                    });
-                }
-
-                resp_data = Some(MeResponseData {
-                    roles: roles.bits(),
-                    user_id: data.owner_id as u64,
-                    user_id_string: data.owner_id.to_string(),
-                    is_banned: data.is_banned,
-                    token: token.to_string(),
-                    discord: user,
-                    mods,
-                    teams,
-                });
-            } else {
-                return Ok(HttpResponse::BadRequest().json(serde_json::json!({
-                    "error": "OAuth2 Session Expired"
-                })));
-            }
-        }
+/// The public profile of a user, their mods and their teams.
+pub async fn public_user(
+    principal: Option<Principal>,
+    user_id: web::Path<i64>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let viewer = principal.and_then(|i| i.user_id().ok());
+
+    match get_public_user(*user_id, viewer, &db).await? {
+        Some(user) => Ok(HttpResponse::Ok().json(user)),
+        None => Ok(HttpResponse::NotFound().body("This user does not exist.")),
     }
+}
 
-    if let Some(data) = resp_data {
-        Ok(HttpResponse::Ok().json(data))
-    } else {
-        Ok(HttpResponse::NoContent().finish())
+/// The public profile page of a user.
+pub async fn profile_page(
+    principal: Option<Principal>,
+    user_id: web::Path<i64>,
+    hb: web::Data<Handlebars<'_>>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let viewer = principal.and_then(|i| i.user_id().ok());
+
+    match get_public_user(*user_id, viewer, &db).await? {
+        Some(user) => {
+            let body = hb.render("user_profile", &user).unwrap();
+
+            Ok(HttpResponse::Ok().body(body))
+        }
+        None => Ok(HttpResponse::NotFound().body("This user does not exist.")),
     }
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrivacyData {
+    profile_public: Option<bool>,
+    show_teams: Option<bool>,
+}
+
+/// Changes what the user shows on their public profile, missing settings are left as they are.
+pub async fn set_privacy(
+    principal: Principal,
+    data: web::Form<PrivacyData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    principal.require_session()?;
+    let user_id = principal.user_id()?;
+
+    sqlx::query!(
+        "UPDATE tokens SET profile_public = COALESCE($2, profile_public), show_teams = COALESCE($3, show_teams) WHERE owner_id = $1",
+        user_id,
+        data.profile_public,
+        data.show_teams,
+    )
+    .execute(&**db)
+    .await?;
+
+    Ok(HttpResponse::Ok().body("Successfully changed your privacy settings."))
+}
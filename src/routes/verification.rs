@@ -1,7 +1,10 @@
+use crate::auth::Principal;
 use crate::error::*;
 use crate::model::*;
-use actix_web::{web, HttpRequest, HttpResponse};
-use sqlx::{postgres::PgDatabaseError, PgPool};
+use crate::utils::audit::AuditEntry;
+use crate::utils::ownership::mod_permissions;
+use actix_web::{web, HttpResponse};
+use sqlx::{PgPool, Postgres, Transaction};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VerifyData {
@@ -11,115 +14,256 @@ pub struct VerifyData {
 }
 
 pub async fn verify(
-    req: HttpRequest,
+    principal: Principal,
     data: web::Query<VerifyData>,
     db: web::Data<PgPool>,
 ) -> ServiceResult<HttpResponse> {
     let pool = &**db;
 
+    principal.require_scope(Scope::Verify)?;
+
+    if !principal.roles.contains(Roles::VERIFIER) {
+        return Ok(HttpResponse::Unauthorized().body("User not allowed to verify."));
+    }
+
+    let verifier_id = principal.id;
+
+    if !data.is_good && data.reason.is_none() {
+        return Ok(HttpResponse::BadRequest()
+            .body("Unable to submit failed verification without a reason."));
+    }
+
+    if let Some(reason) = &data.reason {
+        if !reason.contains(' ') || reason.len() < 60 {
+            return Ok(HttpResponse::BadRequest().body("Invalid or too short of a reason."));
+        }
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    // The version is locked, so concurrent votes on it see each other and the level they settle.
     let query = sqlx::query!(
-        "SELECT owner_id, roles FROM tokens WHERE token = $1",
-        req.headers()
-            .get("Authorization")
-            // unwrap is safe this method only runs when the /api token check has been done.
-            .unwrap()
-            .to_str()
-            .unwrap()
+        r#"SELECT verification as "verification: Verification" FROM mods WHERE checksum = $1 FOR UPDATE"#,
+        &data.checksum,
     )
-    .fetch_optional(pool)
+    .fetch_optional(&mut transaction)
     .await?;
 
-    if let Some(query_data) = query {
-        let roles = Roles::from_bits_truncate(query_data.roles as u32);
-
-        if roles.contains(Roles::VERIFIER) {
-            let query = sqlx::query!(
-                r#"SELECT verification as "verification: Verification" FROM mods WHERE checksum = $1"#,
-                &data.checksum,
-            )
-            .fetch_optional(pool)
-            .await?;
-
-            if let Some(x) = query {
-                if let Some(verification) = x.verification {
-                    if verification == Verification::Core {
-                        return Ok(HttpResponse::BadRequest().body("Cannot verify Core mods."));
-                    } else if verification == Verification::Unsafe {
-                        return Ok(HttpResponse::BadRequest()
-                            .body("This mod has already been verified as Unsafe."));
-                    } else if verification == Verification::Manual {
-                        return Ok(HttpResponse::BadRequest()
-                            .body("This mod has already been manually verified."));
-                    }
-                }
-            } else {
-                return Ok(HttpResponse::BadRequest().body("This mod does not exist."));
-            }
+    let verification = if let Some(x) = query {
+        x.verification.unwrap_or_default()
+    } else {
+        return Ok(HttpResponse::BadRequest().body("This mod does not exist."));
+    };
 
-            if !data.is_good && data.reason.is_none() {
-                return Ok(HttpResponse::BadRequest()
-                    .body("Unable to submit failed verification without a reason."));
-            }
+    let previous_vote = sqlx::query!(
+        "SELECT is_good FROM verification WHERE checksum = $1 AND verifier_id = $2",
+        &data.checksum,
+        verifier_id,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
 
-            if let Some(reason) = &data.reason {
-                if !reason.contains(' ') || reason.len() < 60 {
-                    return Ok(HttpResponse::BadRequest().body("Invalid or too short of a reason."));
-                }
-            }
+    if verification == Verification::Core {
+        return Ok(HttpResponse::BadRequest().body("Cannot verify Core mods."));
+    }
+
+    // Verifiers can always amend their own vote, even after the mod has been settled, as that's
+    // how a wrong verification gets corrected.
+    if previous_vote.is_none() {
+        if verification == Verification::Unsafe {
+            return Ok(
+                HttpResponse::BadRequest().body("This mod has already been verified as Unsafe.")
+            );
+        } else if verification == Verification::Manual {
+            return Ok(
+                HttpResponse::BadRequest().body("This mod has already been manually verified.")
+            );
+        }
+    }
+
+    sqlx::query!(
+        "INSERT INTO verification (checksum, verifier_id, is_good, reason) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (checksum, verifier_id) DO UPDATE
+        SET is_good = EXCLUDED.is_good, reason = EXCLUDED.reason, updated = CURRENT_TIMESTAMP",
+        &data.checksum,
+        verifier_id,
+        &data.is_good,
+        data.reason.as_ref(),
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO verification_history (checksum, verifier_id, action, is_good, reason) VALUES ($1, $2, $3, $4, $5)",
+        &data.checksum,
+        verifier_id,
+        if previous_vote.is_some() { "amend" } else { "submit" },
+        &data.is_good,
+        data.reason.as_ref(),
+    )
+    .execute(&mut transaction)
+    .await?;
 
-            if let Err(why) = sqlx::query!(
-                "INSERT INTO verification (checksum, verifier_id, is_good, reason) VALUES ($1, $2, $3, $4)",
-                &data.checksum,
-                &query_data.owner_id,
-                &data.is_good,
-                data.reason.as_ref(),
-            )
-            .execute(pool)
-            .await {
-                match why {
-                    sqlx::Error::Database(x) => {
-                        if let Some(_constraint) = x.downcast_ref::<PgDatabaseError>().constraint() {
-                            return Ok(HttpResponse::BadRequest().body("You have already submitted a verification for this mod."))
-                        }
-                    }
-                    _ => return Err(why.into())
-                }
-            };
+    AuditEntry {
+        action: "verify",
+        checksum: Some(&data.checksum),
+        details: Some(match (&data.reason, data.is_good) {
+            (Some(reason), true) => format!("Good vote: {}", reason),
+            (Some(reason), false) => format!("Bad vote: {}", reason),
+            (None, _) => "Good vote".to_string(),
+        }),
+        ..Default::default()
+    }
+    .log(verifier_id, &mut transaction)
+    .await?;
+
+    let new_verification = update_verification(&data.checksum, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    if new_verification == verification {
+        if previous_vote.is_some() {
+            Ok(HttpResponse::Ok().body("Successfully amended mod verification."))
         } else {
-            return Ok(HttpResponse::Unauthorized().body("User not allowed to verify."));
+            Ok(HttpResponse::Ok().body("Successfully added mod verification."))
         }
     } else {
-        return Ok(HttpResponse::Unauthorized().body("Token provided not bound to a user."));
+        match new_verification {
+            Verification::Unsafe => {
+                Ok(HttpResponse::Ok().body("Successfully verified mod as Unsafe."))
+            }
+            Verification::Manual => {
+                Ok(HttpResponse::Ok().body("Successfully verified mod as Safe."))
+            }
+            other => Ok(HttpResponse::Ok().body(format!(
+                "Successfully amended mod verification, the mod is now `{}`.",
+                other
+            ))),
+        }
     }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RetractData {
+    checksum: String,
+}
+
+pub async fn retract(
+    principal: Principal,
+    data: web::Query<RetractData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+
+    principal.require_scope(Scope::Verify)?;
+
+    if !principal.roles.contains(Roles::VERIFIER) {
+        return Ok(HttpResponse::Unauthorized().body("User not allowed to verify."));
+    }
+
+    let verifier_id = principal.id;
+
+    let mut transaction = pool.begin().await?;
 
     let query = sqlx::query!(
-        "SELECT id, is_good FROM verification WHERE checksum = $1",
+        "DELETE FROM verification WHERE checksum = $1 AND verifier_id = $2 RETURNING id",
         &data.checksum,
+        verifier_id,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+
+    if query.is_none() {
+        return Ok(
+            HttpResponse::BadRequest().body("You have not submitted a verification for this mod.")
+        );
+    }
+
+    sqlx::query!(
+        "INSERT INTO verification_history (checksum, verifier_id, action) VALUES ($1, $2, 'retract')",
+        &data.checksum,
+        verifier_id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    AuditEntry {
+        action: "retract_verification",
+        checksum: Some(&data.checksum),
+        ..Default::default()
+    }
+    .log(verifier_id, &mut transaction)
+    .await?;
+
+    let verification = update_verification(&data.checksum, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Successfully retracted mod verification, the mod is now `{}`.",
+        verification
+    )))
+}
+
+/// Recomputes the verification level of a mod from the votes currently in `verification`.
+///
+/// Two bad votes make a mod `Unsafe`, two good votes make it `Manual`, and anything less than
+/// that takes an `Unsafe` or `Manual` mod back to `None`.
+/// `Core` mods and mods forced as `Unsafe` by an admin are not decided by votes, so they are left
+/// untouched.
+pub async fn update_verification(
+    checksum: &str,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> ServiceResult<Verification> {
+    let query = sqlx::query!(
+        r#"SELECT verification as "verification: Verification", unsafe_reason FROM mods WHERE checksum = $1"#,
+        checksum,
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    let verification = query.verification.unwrap_or_default();
+
+    if verification == Verification::Core || query.unsafe_reason.is_some() {
+        return Ok(verification);
+    }
+
+    let query = sqlx::query!(
+        "SELECT id, is_good FROM verification WHERE checksum = $1",
+        checksum,
     )
-    .fetch_all(pool)
+    .fetch_all(&mut *transaction)
     .await?;
 
     let (good, bad): (Vec<_>, Vec<_>) = query.iter().partition(|i| i.is_good);
 
-    if bad.len() >= 2 {
-        sqlx::query!(
-            "UPDATE mods SET verification = 'Unsafe' WHERE checksum = $1",
-            &data.checksum,
-        )
-        .execute(pool)
-        .await?;
-        Ok(HttpResponse::Ok().body("Successfully verified mod as Unsafe."))
+    let new_verification = if bad.len() >= 2 {
+        Verification::Unsafe
     } else if good.len() >= 2 {
+        Verification::Manual
+    } else if verification == Verification::Unsafe || verification == Verification::Manual {
+        Verification::None
+    } else {
+        verification.clone()
+    };
+
+    if new_verification != verification {
+        let value = if new_verification == Verification::None {
+            None
+        } else {
+            Some(new_verification.to_string())
+        };
+
         sqlx::query!(
-            "UPDATE mods SET verification = 'Manual' WHERE checksum = $1",
-            &data.checksum,
+            "UPDATE mods SET verification = $1::text::verification_level WHERE checksum = $2",
+            value,
+            checksum,
         )
-        .execute(pool)
+        .execute(&mut *transaction)
         .await?;
-        Ok(HttpResponse::Ok().body("Successfully verified mod as Safe."))
-    } else {
-        Ok(HttpResponse::Ok().body("Successfully added mod verification."))
     }
+
+    Ok(new_verification)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -128,73 +272,97 @@ pub struct YankData {
     reason: Option<String>,
 }
 
+/// Checks if the user or team provided can yank the mod version with the checksum provided.
+async fn can_yank(id: i64, checksum: &str, pool: &PgPool) -> ServiceResult<bool> {
+    let query = sqlx::query!("SELECT name FROM mods WHERE checksum = $1", checksum)
+        .fetch_optional(pool)
+        .await?;
+
+    if let Some(x) = query {
+        let permissions = mod_permissions(id, &x.name, pool).await?;
+
+        return Ok(permissions
+            .map(|i| i.contains(ModPermissions::YANK))
+            .unwrap_or(false));
+    }
+
+    Ok(false)
+}
+
 pub async fn yank(
-    req: HttpRequest,
+    principal: Principal,
     data: web::Query<YankData>,
     db: web::Data<PgPool>,
 ) -> ServiceResult<HttpResponse> {
     let pool = &**db;
 
+    principal.require_scope(Scope::Yank)?;
+
+    if !can_yank(principal.id, &data.checksum, pool).await? {
+        return Err(ServiceError::Unauthorized);
+    }
+
     let query = sqlx::query!(
-        "SELECT owner_id, roles FROM tokens WHERE token = $1",
-        req.headers()
-            .get("Authorization")
-            // unwrap is safe this method only runs when the /api token check has been done.
-            .unwrap()
-            .to_str()
-            .unwrap()
+        "UPDATE mods SET yanked = true, yank_reason = $2, yanked_at = CURRENT_TIMESTAMP, yanked_by = $3 WHERE checksum = $1 AND NOT yanked RETURNING id",
+        &data.checksum,
+        data.reason.as_ref(),
+        principal.id,
     )
     .fetch_optional(pool)
     .await?;
 
-    if let Some(query_data) = query {
-        let query = sqlx::query!("SELECT name FROM mods WHERE checksum = $1", &data.checksum,)
-            .fetch_optional(pool)
-            .await?;
-
-        if let Some(x) = query {
-            let query = sqlx::query!(
-                "SELECT * FROM owners WHERE mod_name = $1 AND owner_id = $2",
-                &x.name,
-                query_data.owner_id,
-            )
-            .fetch_optional(pool)
-            .await?;
-
-            if query.is_some() {
-                if let Err(why) = sqlx::query!(
-                    "INSERT INTO verification (checksum, verifier_id, reason) VALUES ($1, $2, $3)",
-                    &data.checksum,
-                    &query_data.owner_id,
-                    data.reason.as_ref(),
-                )
-                .execute(pool)
-                .await
-                {
-                    match why {
-                        sqlx::Error::Database(x) => {
-                            if let Some(_constraint) =
-                                x.downcast_ref::<PgDatabaseError>().constraint()
-                            {
-                                return Ok(HttpResponse::BadRequest().body(
-                                    "You have already submitted a verification for this mod.",
-                                ));
-                            }
-                        }
-                        _ => return Err(why.into()),
-                    }
-                };
-
-                sqlx::query!(
-                    "UPDATE mods SET verification = 'Yanked' WHERE checksum = $1",
-                    &data.checksum,
-                )
-                .execute(pool)
-                .await?;
-                return Ok(HttpResponse::Ok().body("Successfully yanked mod."));
-            }
+    if query.is_some() {
+        AuditEntry {
+            action: "yank",
+            checksum: Some(&data.checksum),
+            details: data.reason.clone(),
+            ..Default::default()
         }
+        .log(principal.id, pool)
+        .await?;
+
+        Ok(HttpResponse::Ok().body("Successfully yanked mod."))
+    } else {
+        Ok(HttpResponse::BadRequest().body("This mod version has already been yanked."))
     }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnyankData {
+    checksum: String,
+}
 
-    Err(ServiceError::Unauthorized)
+pub async fn unyank(
+    principal: Principal,
+    data: web::Query<UnyankData>,
+    db: web::Data<PgPool>,
+) -> ServiceResult<HttpResponse> {
+    let pool = &**db;
+
+    principal.require_scope(Scope::Yank)?;
+
+    if !can_yank(principal.id, &data.checksum, pool).await? {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    let query = sqlx::query!(
+        "UPDATE mods SET yanked = false, yank_reason = NULL, yanked_at = NULL, yanked_by = NULL WHERE checksum = $1 AND yanked RETURNING id",
+        &data.checksum,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if query.is_some() {
+        AuditEntry {
+            action: "unyank",
+            checksum: Some(&data.checksum),
+            ..Default::default()
+        }
+        .log(principal.id, pool)
+        .await?;
+
+        Ok(HttpResponse::Ok().body("Successfully unyanked mod."))
+    } else {
+        Ok(HttpResponse::BadRequest().body("This mod version is not yanked."))
+    }
 }
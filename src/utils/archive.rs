@@ -0,0 +1,84 @@
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use zip::result::{ZipError, ZipResult};
+use zip::ZipArchive;
+
+/// The most entries a mod archive can have.
+const MAX_ENTRIES: usize = 4096;
+/// The most bytes a single file of a mod archive can decompress to.
+const MAX_FILE_SIZE: u64 = 256 * 1024 * 1024;
+/// The most bytes all the files of a mod archive can decompress to.
+const MAX_TOTAL_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Where the archive of a mod version is stored.
+pub fn archive_path(checksum: &str) -> String {
+    format!(
+        "./files/{}/{}/{}.zip",
+        &checksum[..1],
+        &checksum[..2],
+        checksum
+    )
+}
+
+/// Hashes every file inside of a zip archive.
+///
+/// Returns the name of each file along with the hex encoded SHA-256 of its contents.
+/// Archives with too many entries or that decompress to too much data are refused, the sizes
+/// are counted while decompressing as the ones in the archive can't be trusted.
+/// This is blocking, so it should be ran inside of `web::block`.
+pub fn hash_files(path: &str) -> ZipResult<Vec<(String, String)>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    if archive.len() > MAX_ENTRIES {
+        return Err(ZipError::InvalidArchive("The archive has too many files"));
+    }
+
+    let mut hashes = Vec::with_capacity(archive.len());
+    let mut total_size = 0;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+
+        if file.is_dir() {
+            continue;
+        }
+
+        let mut sh = Sha256::default();
+        let mut buffer = [0u8; 4096];
+        let mut size = 0;
+
+        loop {
+            let n = file.read(&mut buffer)?;
+
+            if n == 0 {
+                break;
+            }
+
+            size += n as u64;
+            total_size += n as u64;
+
+            if size > MAX_FILE_SIZE {
+                return Err(ZipError::InvalidArchive(
+                    "A file of the archive is too large",
+                ));
+            }
+
+            if total_size > MAX_TOTAL_SIZE {
+                return Err(ZipError::InvalidArchive("The archive is too large"));
+            }
+
+            sh.update(&buffer[..n]);
+        }
+
+        let hash = sh
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+
+        hashes.push((file.name().to_string(), hash));
+    }
+
+    Ok(hashes)
+}
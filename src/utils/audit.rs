@@ -0,0 +1,42 @@
+use crate::error::*;
+use sqlx::{Executor, Postgres};
+
+/// An entry of the audit log, the fields that don't apply to the action are left as `None`.
+#[derive(Debug, Default)]
+pub struct AuditEntry<'a> {
+    pub action: &'a str,
+    /// Found from `checksum` when missing.
+    pub mod_name: Option<&'a str>,
+    pub checksum: Option<&'a str>,
+    pub team_id: Option<i32>,
+    /// The account affected, for bans and role changes.
+    pub target_id: Option<i64>,
+    pub details: Option<String>,
+}
+
+impl AuditEntry<'_> {
+    /// Appends the entry to the audit log as an action of `actor_id`.
+    ///
+    /// Pass the transaction of the action when it has one, so the entry is only kept if the
+    /// action is.
+    pub async fn log<'e, E>(&self, actor_id: i64, executor: E) -> ServiceResult<()>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query!(
+            "INSERT INTO audit_log (actor_id, action, mod_name, checksum, team_id, target_id, details)
+            VALUES ($1, $2, COALESCE($3, (SELECT name FROM mods WHERE checksum = $4 LIMIT 1)), $4, $5, $6, $7)",
+            actor_id,
+            self.action,
+            self.mod_name,
+            self.checksum,
+            self.team_id,
+            self.target_id,
+            self.details.as_ref(),
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -3,7 +3,7 @@ use rand::Rng;
 const BASE62: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
 
 pub fn create() -> String {
-    let size = 7;
+    let size = 16;
     let mut id = String::with_capacity(size);
     let mut rng = rand::thread_rng();
 
@@ -1,3 +1,8 @@
-pub mod crypt;
+pub mod archive;
+pub mod audit;
 pub mod invite;
+pub mod names;
+pub mod ownership;
+pub mod profiles;
+pub mod sessions;
 pub mod tokens;
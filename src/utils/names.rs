@@ -0,0 +1,100 @@
+use crate::error::*;
+use sqlx::PgPool;
+
+/// The longest name a new mod can have.
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// The form names are compared in, ignoring case, `-`, `_` and spaces.
+///
+/// Matches `lower(regexp_replace(name, '[-_ ]', '', 'g'))` in the database.
+pub fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|i| !matches!(i, '-' | '_' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Checks the characters and length of the name of a new mod.
+///
+/// Names are made of ASCII letters, digits, `-`, `_` and single spaces, and start and end with a
+/// letter or a digit.
+pub fn check_policy(name: &str) -> Result<(), String> {
+    if name.len() < 2 || name.len() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "Mod names must be between 2 and {} characters.",
+            MAX_NAME_LENGTH
+        ));
+    }
+
+    if !name
+        .chars()
+        .all(|i| i.is_ascii_alphanumeric() || matches!(i, '-' | '_' | ' '))
+    {
+        return Err(
+            "Mod names can only contain ASCII letters, digits, `-`, `_` and spaces.".to_string(),
+        );
+    }
+
+    let first_and_last = [name.chars().next(), name.chars().last()];
+
+    if first_and_last
+        .iter()
+        .any(|i| !i.map(|i| i.is_ascii_alphanumeric()).unwrap_or(false))
+        || name.contains("  ")
+    {
+        return Err(
+            "Mod names must start and end with a letter or a digit, and can't have double spaces."
+                .to_string(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks if a new mod can be registered with this name, returning why not.
+///
+/// Besides the policy, the name can't be the same as or look like the name of another mod once
+/// normalized, and reserved names are kept for Admins and Owners.
+pub async fn check_new_name(
+    name: &str,
+    can_use_reserved: bool,
+    pool: &PgPool,
+) -> ServiceResult<Option<String>> {
+    if let Err(why) = check_policy(name) {
+        return Ok(Some(why));
+    }
+
+    // Skeletons are the normalized names with the characters that look alike replaced, see the
+    // `mod_name_skeleton` function in the database.
+    let query = sqlx::query!(
+        "SELECT name FROM mod_names WHERE skeleton = mod_name_skeleton($1)",
+        name,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(other) = query {
+        return Ok(Some(format!(
+            "This name is too similar to the mod `{}`.",
+            other.name
+        )));
+    }
+
+    if !can_use_reserved {
+        let query = sqlx::query!(
+            "SELECT name FROM reserved_mod_names WHERE skeleton = mod_name_skeleton($1) LIMIT 1",
+            name,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        if let Some(other) = query {
+            return Ok(Some(format!(
+                "This name is too similar to the reserved name `{}`.",
+                other.name
+            )));
+        }
+    }
+
+    Ok(None)
+}
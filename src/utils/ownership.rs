@@ -0,0 +1,126 @@
+use crate::error::*;
+use crate::model::*;
+use sqlx::PgPool;
+
+/// Creates the account of a team, that holds its tokens and bans, if it doesn't have one yet.
+///
+/// Teams only get an account once one is needed, and nobody logs in as them, so the email is only
+/// a placeholder.
+pub async fn create_team_account(team_id: i64, pool: &PgPool) -> ServiceResult<()> {
+    sqlx::query!(
+        "INSERT INTO tokens (owner_id, email, is_team) VALUES ($1, $2, true) ON CONFLICT (owner_id) DO NOTHING",
+        team_id,
+        &format!("team-{}@local", team_id),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets what a user or team can do with a mod, `None` if nobody owns the mod yet.
+///
+/// Owners can do everything. Members of the teams that own a mod get the permissions set for
+/// them on the mod, or the ones of their team roles, unless the team is banned.
+pub async fn mod_permissions(
+    id: i64,
+    mod_name: &str,
+    pool: &PgPool,
+) -> ServiceResult<Option<ModPermissions>> {
+    let owners = sqlx::query!(
+        "SELECT owner_id, is_team FROM owners WHERE mod_name = $1",
+        mod_name,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if owners.is_empty() {
+        return Ok(None);
+    }
+
+    if owners.iter().any(|i| i.owner_id == id) {
+        return Ok(Some(ModPermissions::all()));
+    }
+
+    let teams = owners
+        .iter()
+        .filter(|i| i.is_team)
+        .map(|i| i.owner_id)
+        .collect::<Vec<_>>();
+
+    if teams.is_empty() {
+        return Ok(Some(ModPermissions::empty()));
+    }
+
+    let query = sqlx::query!(
+        r#"
+            SELECT
+                m.roles,
+                p.permissions as "permissions?"
+            FROM
+                team_members m
+                LEFT JOIN team_mod_permissions p ON p.team_id = m.team_id
+                AND p.member = m.member
+                AND p.mod_name = $3
+                LEFT JOIN tokens t ON t.owner_id = m.team_id::BIGINT
+            WHERE
+                m.team_id::BIGINT = ANY($1)
+                AND m.member = $2
+                AND NOT COALESCE(t.is_banned AND (t.ban_expires IS NULL OR t.ban_expires > CURRENT_TIMESTAMP), false)
+        "#,
+        &teams,
+        id,
+        mod_name,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let permissions = query
+        .into_iter()
+        .fold(ModPermissions::empty(), |acc, member| {
+            acc | match member.permissions {
+                Some(x) => ModPermissions::from_bits_truncate(x as u32),
+                None => TeamRoles::from_bits_truncate(member.roles as u32).mod_permissions(),
+            }
+        });
+
+    Ok(Some(permissions))
+}
+
+/// Gets the owner of a mod a user or team can manage the ownership of the mod as.
+///
+/// That's themselves if they own the mod, or a team that owns it where they are an Owner or an
+/// Admin, unless the team is banned.
+pub async fn acting_owner(id: i64, mod_name: &str, pool: &PgPool) -> ServiceResult<Option<i64>> {
+    let query = sqlx::query!(
+        r#"
+            SELECT
+                o.owner_id
+            FROM
+                owners o
+                LEFT JOIN team_members m ON o.is_team
+                AND m.team_id::BIGINT = o.owner_id
+                AND m.member = $2
+                LEFT JOIN tokens t ON t.owner_id = m.team_id::BIGINT
+            WHERE
+                o.mod_name = $1
+                AND (
+                    o.owner_id = $2
+                    OR (
+                        (m.roles & $3) != 0
+                        AND NOT COALESCE(t.is_banned AND (t.ban_expires IS NULL OR t.ban_expires > CURRENT_TIMESTAMP), false)
+                    )
+                )
+            ORDER BY
+                o.owner_id = $2 DESC
+            LIMIT 1
+        "#,
+        mod_name,
+        id,
+        (TeamRoles::OWNER | TeamRoles::ADMIN).bits() as i32,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(query.map(|i| i.owner_id))
+}
@@ -0,0 +1,184 @@
+use crate::error::*;
+use crate::model::*;
+use crate::providers::{ExternalProfile, Providers};
+use sqlx::PgPool;
+
+/// The profile shown for a user, from the account they last logged in with.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Profile {
+    pub username: String,
+    /// Discord only.
+    pub discriminator: Option<String>,
+    pub avatar_url: Option<String>,
+    /// The provider the profile comes from, `None` if the user never logged in since profiles
+    /// started being stored.
+    pub provider: Option<String>,
+}
+
+/// Stores the OAuth2 tokens of a linked account, so the profile can be refreshed later.
+///
+/// The old refresh token is kept when the provider doesn't send a new one.
+pub async fn store_oauth_tokens(
+    provider: &str,
+    external_id: &str,
+    tokens: &OAuthResponse,
+    pool: &PgPool,
+) -> ServiceResult<()> {
+    sqlx::query!(
+        "UPDATE linked_accounts SET access_token = $3, refresh_token = COALESCE($4, refresh_token), token_expires = CURRENT_TIMESTAMP + make_interval(secs => $5) WHERE provider = $1 AND external_id = $2",
+        provider,
+        external_id,
+        &tokens.access_token,
+        tokens.refresh_token.as_ref(),
+        tokens.expires_in.map(f64::from),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Replaces the stored profile of a user.
+pub async fn update_profile(
+    user_id: i64,
+    provider: &str,
+    profile: &ExternalProfile,
+    pool: &PgPool,
+) -> ServiceResult<()> {
+    sqlx::query!(
+        "UPDATE tokens SET username = $2, discriminator = $3, avatar_url = $4, profile_provider = $5, profile_updated = CURRENT_TIMESTAMP WHERE owner_id = $1",
+        user_id,
+        &profile.username,
+        profile.discriminator.as_ref(),
+        profile.avatar_url.as_ref(),
+        provider,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE linked_accounts SET username = $3, email = $4 WHERE provider = $1 AND external_id = $2",
+        provider,
+        &profile.id,
+        &profile.username,
+        profile.email.as_ref(),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches the profile of a user again from the provider, using the stored OAuth2 tokens.
+///
+/// Returns `None` if the provider is gone or the tokens can't be used anymore.
+async fn refresh_profile(
+    user_id: i64,
+    provider_name: &str,
+    providers: &Providers,
+    pool: &PgPool,
+) -> ServiceResult<Option<ExternalProfile>> {
+    let provider = if let Some(x) = providers.get(provider_name) {
+        x
+    } else {
+        return Ok(None);
+    };
+
+    let query = sqlx::query!(
+        r#"
+            SELECT
+                external_id,
+                access_token,
+                refresh_token,
+                COALESCE(token_expires < CURRENT_TIMESTAMP, false) as "expired!"
+            FROM
+                linked_accounts
+            WHERE
+                user_id = $1
+                AND provider = $2
+        "#,
+        user_id,
+        provider_name,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let account = if let Some(x) = query {
+        x
+    } else {
+        return Ok(None);
+    };
+
+    let access_token = match (account.access_token, account.refresh_token) {
+        (Some(token), _) if !account.expired => token,
+        (_, Some(refresh_token)) => {
+            let tokens = provider.refresh(&refresh_token).await?;
+            store_oauth_tokens(provider_name, &account.external_id, &tokens, pool).await?;
+
+            tokens.access_token
+        }
+        _ => return Ok(None),
+    };
+
+    let profile = provider.get_profile(&access_token).await?;
+    update_profile(user_id, provider_name, &profile, pool).await?;
+
+    Ok(Some(profile))
+}
+
+/// Gets the stored profile of a user, refreshing it from the provider once a day.
+///
+/// Never requires the user to be logged in, if the refresh fails the stored profile is used for
+/// another day.
+pub async fn get_profile(
+    user_id: i64,
+    providers: &Providers,
+    pool: &PgPool,
+) -> ServiceResult<Profile> {
+    let query = sqlx::query!(
+        r#"
+            SELECT
+                username,
+                discriminator,
+                avatar_url,
+                profile_provider,
+                COALESCE(profile_updated < CURRENT_TIMESTAMP - INTERVAL '1 day', true) as "stale!"
+            FROM
+                tokens
+            WHERE
+                owner_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if let (true, Some(provider)) = (query.stale, &query.profile_provider) {
+        match refresh_profile(user_id, provider, providers, pool).await {
+            Ok(Some(profile)) => {
+                return Ok(Profile {
+                    username: profile.username,
+                    discriminator: profile.discriminator,
+                    avatar_url: profile.avatar_url,
+                    provider: Some(provider.to_string()),
+                });
+            }
+            Ok(None) => (),
+            Err(why) => warn!("Could not refresh the profile of {}: {}", user_id, why),
+        }
+
+        sqlx::query!(
+            "UPDATE tokens SET profile_updated = CURRENT_TIMESTAMP WHERE owner_id = $1",
+            user_id,
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(Profile {
+        username: query.username.unwrap_or_else(|| user_id.to_string()),
+        discriminator: query.discriminator,
+        avatar_url: query.avatar_url,
+        provider: query.profile_provider,
+    })
+}
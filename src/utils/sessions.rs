@@ -0,0 +1,79 @@
+use crate::error::*;
+use crate::utils::tokens::{gen_secret, hash_token, request_ip};
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use sqlx::PgPool;
+
+/// Days a session lasts for, the identity cookie lasts as long.
+pub const SESSION_DAYS: i32 = 7;
+
+/// Starts a new session for the user, returning the session id to store in the cookie.
+///
+/// Like API tokens, only a hash of the session id is stored. Expired sessions of the user are
+/// cleaned up here.
+pub async fn create_session(
+    req: &HttpRequest,
+    user_id: i64,
+    provider: &str,
+    pool: &PgPool,
+) -> ServiceResult<String> {
+    let session = gen_secret(40);
+
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|i| i.to_str().ok())
+        .map(|i| i.chars().take(256).collect::<String>());
+
+    sqlx::query!(
+        "DELETE FROM sessions WHERE user_id = $1 AND expires < CURRENT_TIMESTAMP",
+        user_id,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO sessions (user_id, session_hash, provider, ip, user_agent, expires) VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP + make_interval(days => $6))",
+        user_id,
+        hash_token(&session),
+        provider,
+        request_ip(req),
+        user_agent,
+        SESSION_DAYS,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(session)
+}
+
+/// Ends a session, used when logging out.
+pub async fn delete_session(session: &str, pool: &PgPool) -> ServiceResult<()> {
+    sqlx::query!(
+        "DELETE FROM sessions WHERE session_hash = $1",
+        hash_token(session),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Updates when and from where a session was last used.
+///
+/// Only done every 5 minutes, so not every request writes to the database.
+pub async fn mark_session_seen(
+    session_id: i32,
+    ip: Option<&str>,
+    pool: &PgPool,
+) -> ServiceResult<()> {
+    sqlx::query!(
+        "UPDATE sessions SET last_seen = CURRENT_TIMESTAMP, ip = COALESCE($2, ip) WHERE id = $1 AND last_seen < CURRENT_TIMESTAMP - INTERVAL '5 minutes'",
+        session_id,
+        ip,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -1,30 +1,158 @@
-use crate::utils::crypt::*;
+use crate::error::*;
+use crate::model::*;
+use actix_web::HttpRequest;
+use rand::rngs::OsRng;
+use rand::Rng;
 use sha2::{Digest, Sha256};
-use std::time::SystemTime;
+use sqlx::PgPool;
 
-use base64::encode;
-use std::convert::TryInto;
+/// Prefix of every token, so leaked tokens are easy to recognise and search for.
+pub const TOKEN_PREFIX: &str = "pm_";
 
-pub fn gen_token(user_id: u64, email: impl ToString, key: &[u8], iv: &[u8]) -> Option<String> {
-    let key: [u8; 32] = key.try_into().ok()?;
-    let iv: [u8; 16] = iv.try_into().ok()?;
+const BASE62: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
 
-    let time = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .ok()?
-        .as_micros();
+/// Generates a random string from the OS random number generator, for anything used as a secret.
+pub fn gen_secret(size: usize) -> String {
+    (0..size)
+        .map(|_| BASE62[OsRng.gen_range(0..BASE62.len())] as char)
+        .collect()
+}
+
+/// Generates a new API token.
+///
+/// Only the hash of the token should ever be stored, see `hash_token`.
+pub fn gen_token() -> String {
+    format!("{}{}", TOKEN_PREFIX, gen_secret(40))
+}
+
+/// Hashes a token into the form stored in the database.
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>()
+}
+
+/// Gets the IP address of the client, for the security log.
+pub fn request_ip(req: &HttpRequest) -> Option<String> {
+    req.connection_info()
+        .realip_remote_addr()
+        .map(|i| i.to_string())
+}
+
+/// Records an event of a token in the security log of its owner.
+///
+/// `event` is one of `created`, `rotated`, `revoked` or `used`.
+pub async fn log_token_event(
+    owner_id: i64,
+    token_id: i32,
+    token_name: &str,
+    event: &str,
+    ip: Option<&str>,
+    pool: &PgPool,
+) -> ServiceResult<()> {
+    sqlx::query!(
+        "INSERT INTO token_events (owner_id, token_id, token_name, event, ip) VALUES ($1, $2, $3, $4, $5)",
+        owner_id,
+        token_id,
+        token_name,
+        event,
+        ip,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Updates the last time the token was used.
+///
+/// Uses are only added to the security log once an hour, so the log isn't flooded by every
+/// request.
+pub async fn mark_token_used(token_id: i32, ip: Option<&str>, pool: &PgPool) -> ServiceResult<()> {
+    sqlx::query!(
+        "INSERT INTO token_events (owner_id, token_id, token_name, event, ip)
+        SELECT owner_id, id, name, 'used', $2 FROM api_tokens
+        WHERE id = $1 AND (last_used IS NULL OR last_used < CURRENT_TIMESTAMP - INTERVAL '1 hour')",
+        token_id,
+        ip,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE api_tokens SET last_used = CURRENT_TIMESTAMP WHERE id = $1",
+        token_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Generates a new token for the account and stores its hash, returning the token itself.
+///
+/// The token expires after the number of days provided, or never if it's `None`.
+pub async fn create_token(
+    owner_id: i64,
+    name: &str,
+    scopes: &[Scope],
+    expires_in: Option<i32>,
+    ip: Option<&str>,
+    pool: &PgPool,
+) -> ServiceResult<String> {
+    let token = gen_token();
+
+    let query = sqlx::query!(
+        "INSERT INTO api_tokens (owner_id, name, token_hash, scopes, expires) VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP + make_interval(days => $5)) RETURNING id",
+        owner_id,
+        name,
+        &hash_token(&token),
+        &scopes.iter().map(|i| i.to_string()).collect::<Vec<_>>(),
+        expires_in,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    log_token_event(owner_id, query.id, name, "created", ip, pool).await?;
+
+    Ok(token)
+}
+
+/// Replaces a token of the account with a new one, keeping its name, scopes and expiry.
+///
+/// The old token keeps working for the number of hours of the grace period, so it can be
+/// replaced wherever it's used. A grace period of 0 revokes it immediately.
+/// Returns `None` if the account has no token with that id.
+pub async fn rotate_token(
+    owner_id: i64,
+    token_id: i32,
+    grace_period: i32,
+    ip: Option<&str>,
+    pool: &PgPool,
+) -> ServiceResult<Option<String>> {
+    let token = gen_token();
 
-    let data = Sha256::digest(
-        &format!(
-            "{}:{}",
-            email.to_string(),
-            user_id as u128 ^ (time % user_id as u128),
-        )
-        .as_bytes(),
-    );
+    let query = sqlx::query!(
+        "UPDATE api_tokens SET
+            previous_token_hash = CASE WHEN $4 > 0 THEN token_hash END,
+            previous_expires = CASE WHEN $4 > 0 THEN CURRENT_TIMESTAMP + make_interval(hours => $4) END,
+            token_hash = $3
+        WHERE id = $1 AND owner_id = $2
+        RETURNING name",
+        token_id,
+        owner_id,
+        &hash_token(&token),
+        grace_period,
+    )
+    .fetch_optional(pool)
+    .await?;
 
-    let encrypted_data = encrypt_bytes(data.as_slice(), key, iv).ok()?;
-    let encrypted_data_text = encode(encrypted_data.to_vec());
+    if let Some(data) = query {
+        log_token_event(owner_id, token_id, &data.name, "rotated", ip, pool).await?;
 
-    Some(encrypted_data_text)
+        Ok(Some(token))
+    } else {
+        Ok(None)
+    }
 }
@@ -48,6 +48,21 @@ if (level.alert) {
     document.getElementById("verification_warnings").innerHTML = level.alert;
 }
 
+let yanked = document.getElementById("yanked").innerText == "true"
+let yank_reason = document.getElementById("yank_reason").innerText
+
+if (yanked) {
+    verif_detail.innerHTML += " " + verificationProperties.Yanked.badge
+    document.getElementById("verification_warnings").innerHTML += verificationProperties.Yanked.alert
+
+    if (yank_reason) {
+        document.getElementById("verification_warnings").innerHTML += `<p class="mb-3"><b>Reason: </b>${safetext(yank_reason)}</p>`
+    }
+
+    document.getElementById("yank_button").hidden = true
+    document.getElementById("unyank_button").hidden = false
+}
+
 function formUpdate(){
     is_good = document.getElementsByName("is_good")[0]
     reason = document.getElementById("reason")
@@ -73,11 +88,10 @@ function submitVerification(){
     
     fetch("./api/verify?" + params, 
         {
+            method: "post",
             mode: "same-origin",
             credentials: "include",
-            headers: {
-                "Authorization": getToken()
-            }
+            headers: authHeaders()
         }
     ).then(
         function (response){
@@ -129,12 +143,14 @@ function transferMod(){
     f.set("team_id", transfer_target.selectedOptions[0].value)
     f.set("mod", document.getElementById("mod_name").textContent)
     let params = new URLSearchParams(f).toString()
-    fetch("./public_api/teams/transfer_mod?" + params, 
+    fetch("./public_api/teams/transfer_mod", 
         {
-            method: "get",
+            method: "post",
             headers: {
+                "Content-Type":"application/x-www-form-urlencoded",
                 "accept-charset":"utf-8"
             },
+            body: params
         }
     ).then(response => {
         if (response.status === 200){
@@ -165,11 +181,10 @@ function yankMod(){
     let params = new URLSearchParams(f).toString()
     fetch("./api/yank?" + params,
         {
+            method: "post",
             mode: "same-origin",
             credentials: "include",
-            headers: {
-                "Authorization": getToken()
-            }
+            headers: authHeaders()
         }
     ).then(response => {
         if (response.status === 200){
@@ -189,4 +204,28 @@ function yankMod(){
             })
         }
     })
-}
\ No newline at end of file
+}
+
+function unyankMod(){
+    let checksum = document.getElementById("files").textContent.split("/")[3]
+    let alerts = document.getElementById("verification_warnings")
+    let f = new FormData()
+    f.set("checksum", checksum)
+    let params = new URLSearchParams(f).toString()
+    fetch("./api/unyank?" + params,
+        {
+            method: "post",
+            mode: "same-origin",
+            credentials: "include",
+            headers: authHeaders()
+        }
+    ).then(response => {
+        response.text().then(text => {
+            let colour = response.status === 200 ? "alert-success" : "alert-danger"
+            alerts.innerHTML += `<div class="alert ${colour} alert-dismissible fade show" role="alert">
+            ${safetext(text)}
+            <button type="button" class="btn-close" data-bs-dismiss="alert" aria-label="Close"></button>
+            </div>`
+        })
+    })
+}
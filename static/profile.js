@@ -1,19 +1,43 @@
-document.getElementById("username").innerText = data.discord.username
-document.getElementById("profile_image").src = `https://cdn.discordapp.com/avatars/${data.user_id_string}/${data.discord.avatar}.png?size=256`
+document.getElementById("username").innerText = data.profile.username
+document.getElementById("profile_image").src = data.profile.avatar_url || "/static/PolyTech.svg"
+document.getElementById("public_profile_link").href = `/users/${data.user_id_string}`
+document.getElementById("profile_public_input").checked = data.privacy.profile_public
+document.getElementById("show_teams_input").checked = data.privacy.show_teams
+if (data.pending_verifications !== undefined){
+    let pending = document.getElementById("pending_verifications")
+    pending.innerText = `${data.pending_verifications} versions waiting for your verification`
+    pending.hidden = false
+}
 
 let mods_select = document.getElementById("mods_select")
 let teams_select = document.getElementById("teams_select")
+let tokens_select = document.getElementById("tokens_select")
+let sessions_select = document.getElementById("sessions_select")
 
 let mod_count = document.getElementById("mod_count")
 let team_count = document.getElementById("team_count")
+let token_count = document.getElementById("token_count")
+let session_count = document.getElementById("session_count")
 
 let mods_display = document.getElementById("mods_display")
 let teams_display = document.getElementById("teams_display")
+let tokens_display = document.getElementById("tokens_display")
+let tokens_table = document.getElementById("tokens_table")
+let token_events_table = document.getElementById("token_events_table")
+let sessions_display = document.getElementById("sessions_display")
+let sessions_table = document.getElementById("sessions_table")
+let revoke_other_sessions_button = document.getElementById("revoke_other_sessions_button")
 let create_team_button = document.getElementById("create_team_button")
+let create_token_button = document.getElementById("create_token_button")
 
 
 let create_team_alerts = document.getElementById("create_team_alerts")
 let join_team_alerts = document.getElementById("join_team_alerts")
+let create_token_alerts = document.getElementById("create_token_alerts")
+let tokens_alerts = document.getElementById("tokens_alerts")
+let sessions_alerts = document.getElementById("sessions_alerts")
+let accounts_alerts = document.getElementById("accounts_alerts")
+let accounts_list = document.getElementById("accounts_list")
 
 function addModCard(mod) {
     //console.log(mod)
@@ -35,7 +59,7 @@ function addModCard(mod) {
             */
     card = document.createElement("div");
     card.setAttribute("role", "button");
-    card.setAttribute("onclick", `document.location.href = '/mod?name=${mod.name}&version==${mod.version}&verification=${mod.verification}'`);
+    card.setAttribute("onclick", `document.location.href = '/mod?name=${mod.name}&version==${mod.version}&verification=${mod.verification}&include_yanked=${mod.yanked}'`);
     card.classList.add("card", "mx-2");
 
     body = document.createElement("div");
@@ -58,6 +82,13 @@ function addModCard(mod) {
 
     labels = document.createElement("div");
     labels.innerHTML = verificationProperties.fromVerificationLevel(mod["verification"]).badge;
+    if (mod.deprecated !== undefined){
+        labels.innerHTML += ` <span class="badge bg-warning text-dark">Deprecated</span>`
+    }
+    if (mod.team_id !== undefined){
+        let team = data.teams.find(team => team.id == mod.team_id)
+        labels.innerHTML += ` <span class="badge bg-secondary">${safetext(team ? team.name : "Team")}</span>`
+    }
 
     //result.categories.forEach((category) => {
     //  span = document.createElement("span");
@@ -94,7 +125,10 @@ function addTeamCard(team){
     body.appendChild(content);
 
     let title = document.createElement("h5");
-    title.innerHTML = safetext(team.name);
+    let link = document.createElement("a");
+    link.href = `/teams/${team.id}`
+    link.innerText = team.name
+    title.appendChild(link)
 
     let details = document.createElement("p")
     details.innerHTML = (team.roles & TeamRoles.OWNER ? "Owner" : (team.roles & TeamRoles.ADMIN ? "Admin" : (team.roles & TeamRoles.MOD ? "Mod" : "No permissions")))
@@ -123,10 +157,228 @@ function addTeamCard(team){
     
     content.appendChild(title);
     content.appendChild(details)
-    content.appendChild(inviteButton)
+    if (team.roles & (TeamRoles.OWNER | TeamRoles.ADMIN | TeamRoles.MOD)){
+        content.appendChild(inviteButton)
+    }
+
+    if (team.roles & (TeamRoles.OWNER | TeamRoles.ADMIN)){
+        let rotateButton = document.createElement("button")
+        rotateButton.innerText = "Rotate token"
+        rotateButton.onclick = e => {
+            rotateTeamToken(team)
+        }
+        rotateButton.classList.add("btn", "btn-light", "ms-2")
+        content.appendChild(rotateButton)
+    }
+
+    let membersButton = document.createElement("button")
+    membersButton.innerText = "Members"
+    membersButton.onclick = e => {
+        refreshMembers(team)
+    }
+    membersButton.classList.add("btn", "btn-light", "ms-2")
+    membersButton.setAttribute("data-bs-toggle", "modal")
+    membersButton.setAttribute("data-bs-target", "#team_members_modal")
+    content.appendChild(membersButton)
+
+    if (team.roles & TeamRoles.OWNER){
+        let editButton = document.createElement("button")
+        editButton.innerText = "Edit"
+        editButton.onclick = e => editTeam(team)
+        editButton.classList.add("btn", "btn-light", "ms-2")
+        content.appendChild(editButton)
+    }
+
+    let leaveButton = document.createElement("button")
+    if (team.roles & TeamRoles.OWNER){
+        leaveButton.innerText = "Delete"
+        leaveButton.onclick = e => deleteTeam(team)
+    }
+    else {
+        leaveButton.innerText = "Leave"
+        leaveButton.onclick = e => leaveTeam(team)
+    }
+    leaveButton.classList.add("btn", "btn-outline-danger", "ms-2")
+    content.appendChild(leaveButton)
+
     teams_display.appendChild(card);
 }
 
+function teamRoleName(roles){
+    return roles & TeamRoles.OWNER ? "Owner" : (roles & TeamRoles.ADMIN ? "Admin" : (roles & TeamRoles.MOD ? "Mod" : "Member"))
+}
+
+function teamRank(roles){
+    return roles & TeamRoles.OWNER ? 3 : (roles & TeamRoles.ADMIN ? 2 : (roles & TeamRoles.MOD ? 1 : 0))
+}
+
+function postTeamForm(url, fields){
+    let f = new FormData()
+    for (let key in fields){
+        f.set(key, fields[key])
+    }
+    let params = new URLSearchParams(f).toString()
+
+    return fetch(url,
+        {
+            method: "post",
+            headers: {
+                "Content-Type":"application/x-www-form-urlencoded",
+                "accept-charset":"utf-8"
+            },
+            body: params
+        }
+    )
+}
+
+function showMembersAlert(kind, text){
+    document.getElementById("team_members_alerts").innerHTML = `<div class="alert alert-${kind} alert-dismissible fade show" role="alert">
+        ${safetext(text)}
+        <button type="button" class="btn-close" data-bs-dismiss="alert" aria-label="Close"></button>
+    </div>`
+}
+
+function addMemberRow(team, member){
+    let table = document.getElementById("team_members_table")
+    let row = document.createElement("tr")
+
+    let name = document.createElement("td")
+    name.innerText = member.username
+    row.appendChild(name)
+
+    let actions = document.createElement("td")
+    let rank = teamRank(team.roles)
+
+    if (member.id != data.user_id_string && rank >= 2 && rank > teamRank(member.roles)){
+        let select = document.createElement("select")
+        select.classList.add("form-select", "form-select-sm", "d-inline-block", "w-auto")
+        let roles = rank == 3 ? ["admin", "mod", "member"] : ["mod", "member"]
+        roles.forEach(role => {
+            let option = document.createElement("option")
+            option.value = role
+            option.innerText = role.charAt(0).toUpperCase() + role.slice(1)
+            option.selected = teamRoleName(member.roles).toLowerCase() == role
+            select.appendChild(option)
+        })
+        select.onchange = e => setMemberRole(team, member, select.value)
+        actions.appendChild(select)
+
+        let kick = document.createElement("button")
+        kick.classList.add("btn", "btn-sm", "btn-outline-danger", "ms-2")
+        kick.innerText = "Remove"
+        kick.onclick = e => kickMember(team, member)
+        actions.appendChild(kick)
+    }
+    else {
+        actions.innerText = teamRoleName(member.roles)
+    }
+
+    if (member.id != data.user_id_string && rank == 3){
+        let transfer = document.createElement("button")
+        transfer.classList.add("btn", "btn-sm", "btn-outline-warning", "ms-2")
+        transfer.innerText = "Make owner"
+        transfer.onclick = e => transferTeam(team, member)
+        actions.appendChild(transfer)
+    }
+    row.appendChild(actions)
+
+    table.appendChild(row)
+}
+
+function refreshMembers(team){
+    let table = document.getElementById("team_members_table")
+    document.getElementById("team_members_modal_label").innerText = `Members of ${team.name}`
+
+    fetch(`./public_api/teams/members?team_id=${team.id}`).then(response => {
+        if (response.status === 200){
+            response.json().then(members => {
+                table.innerHTML = ""
+                members.forEach(member => {
+                    addMemberRow(team, member)
+                })
+            })
+        }
+        else {
+            response.text().then(text => showMembersAlert("danger", text))
+        }
+    })
+}
+
+function kickMember(team, member){
+    if (!confirm(`Remove ${member.username} from ${team.name}?`)) return
+
+    postTeamForm("./public_api/teams/kick", {team_id: team.id, member: member.id}).then(response => {
+        response.text().then(text => {
+            showMembersAlert(response.status === 200 ? "success" : "danger", text)
+        })
+        refreshMembers(team)
+    })
+}
+
+function setMemberRole(team, member, role){
+    postTeamForm("./public_api/teams/role", {team_id: team.id, member: member.id, role: role}).then(response => {
+        response.text().then(text => {
+            showMembersAlert(response.status === 200 ? "success" : "danger", text)
+        })
+        refreshMembers(team)
+    })
+}
+
+function transferTeam(team, member){
+    if (!confirm(`Make ${member.username} the owner of ${team.name}? You will become an Admin.`)) return
+
+    postTeamForm("./public_api/teams/transfer", {team_id: team.id, member: member.id}).then(response => {
+        response.text().then(text => {
+            showMembersAlert(response.status === 200 ? "success" : "danger", text)
+        })
+        if (response.status === 200){
+            team.roles = TeamRoles.ADMIN
+            reloadMe()
+        }
+        refreshMembers(team)
+    })
+}
+
+function editTeam(team){
+    fetch(`./public_api/teams/${team.id}`).then(response => response.json()).then(current => {
+        let name = prompt("Name", current.name)
+        if (name == null) return
+        let description = prompt("Description, leave it empty to remove it", current.description || "")
+        if (description == null) return
+        let avatar_url = prompt("Avatar url (https), leave it empty to remove it", current.avatar_url || "")
+        if (avatar_url == null) return
+
+        postTeamForm("./public_api/teams/edit", {team_id: team.id, name: name, description: description, avatar_url: avatar_url}).then(response => {
+            response.text().then(text => {
+                alert(text)
+            })
+            if (response.status === 200) reloadMe()
+        })
+    })
+}
+
+function leaveTeam(team){
+    if (!confirm(`Leave ${team.name}?`)) return
+
+    postTeamForm("./public_api/teams/leave", {team_id: team.id}).then(response => {
+        response.text().then(text => {
+            alert(text)
+        })
+        if (response.status === 200) reloadMe()
+    })
+}
+
+function deleteTeam(team){
+    if (!confirm(`Delete ${team.name}? The mods of the team will be transferred to you, and its token will stop working.`)) return
+
+    postTeamForm("./public_api/teams/delete", {team_id: team.id}).then(response => {
+        response.text().then(text => {
+            alert(text)
+        })
+        if (response.status === 200) reloadMe()
+    })
+}
+
 function createTeam(){
     let form = document.getElementById("create_team_form")
     if (!form.reportValidity()) return
@@ -163,39 +415,533 @@ function createTeam(){
     })
 }
 
+function rotateTeamToken(team){
+    let grace_period = prompt(`Rotating the token of ${team.name}. For how many hours should the old token keep working? (0 - 168)`, "0")
+    if (grace_period == null) return
+
+    let f = new FormData()
+    f.set("id", team.id)
+    f.set("grace_period", grace_period)
+    let params = new URLSearchParams(f).toString()
+
+    fetch("./public_api/teams/token/rotate",
+        {
+            method: "post",
+            headers: {
+                "Content-Type":"application/x-www-form-urlencoded",
+                "accept-charset":"utf-8"
+            },
+            body: params
+        }
+    ).then(response => {
+        response.text().then(text => {
+            if (response.status === 200){
+                if (localStorage.as_team == "true" && localStorage.team_id == team.id){
+                    localStorage.team_token = text
+                }
+                alert(`The new token of ${team.name} will only be shown once: ${text}`)
+            }
+            else {
+                alert(text)
+            }
+        })
+    })
+}
+
+let invite_team
+
 function getInvite(team){
+    invite_team = team
     let team_invite = document.getElementById("team_invite")
     team_invite.innerText = ""
     team_invite.href = ""
+    document.getElementById("team_invite_modal_alerts").innerHTML = ""
+    document.getElementById("team_invite_modal_label").innerText = `Invites of ${team.name}`
+    refreshInvites(team)
+}
+
+function showInviteAlert(kind, text){
+    document.getElementById("team_invite_modal_alerts").innerHTML = `<div class="alert alert-${kind} alert-dismissible fade show" role="alert">
+        ${safetext(text)}
+        <button type="button" class="btn-close" data-bs-dismiss="alert" aria-label="Close"></button>
+    </div>`
+}
+
+function createInvite(){
+    let form = document.getElementById("team_invite_form")
+    if (!form.reportValidity()) return
+
+    let fields = {
+        team_id: invite_team.id,
+        days: document.getElementById("invite_days").value,
+    }
+    let max_uses = document.getElementById("invite_max_uses").value
+    if (max_uses) fields.max_uses = max_uses
+    let target = document.getElementById("invite_target").value
+    if (target) fields.target = target
+    let role = document.getElementById("invite_role").value
+    if (role != "member") fields.role = role
+
+    postTeamForm("./public_api/teams/invite", fields).then(response => {
+        response.text().then(text => {
+            let team_invite = document.getElementById("team_invite")
+            if (response.status === 200){
+                team_invite.innerText = text
+                team_invite.href = text
+            }
+            else {
+                showInviteAlert("danger", text)
+            }
+        })
+        refreshInvites(invite_team)
+    }).catch(error => {
+        showInviteAlert("danger", error)
+    })
+}
+
+function addInviteRow(team, invite){
+    let table = document.getElementById("team_invites_table")
+    let row = document.createElement("tr")
+
+    let cells = [
+        invite.code,
+        invite.max_uses ? `${invite.uses} / ${invite.max_uses}` : `${invite.uses}`,
+        invite.active ? (invite.expires ? new Date(invite.expires).toLocaleString() : "Never") : "Inactive",
+        invite.joined.map(i => i.username).join(", "),
+    ]
+
+    cells.forEach(text => {
+        let cell = document.createElement("td")
+        cell.innerText = text
+        row.appendChild(cell)
+    })
+
+    let actions = document.createElement("td")
+    if (invite.active){
+        let revoke = document.createElement("button")
+        revoke.classList.add("btn", "btn-sm", "btn-outline-danger")
+        revoke.innerText = "Revoke"
+        revoke.onclick = e => revokeInvite(team, invite)
+        actions.appendChild(revoke)
+    }
+    row.appendChild(actions)
+
+    table.appendChild(row)
+}
+
+function refreshInvites(team){
+    fetch(`./public_api/teams/invites?team_id=${team.id}`).then(response => {
+        if (response.status === 200){
+            response.json().then(invites => {
+                document.getElementById("team_invites_table").innerHTML = ""
+                invites.forEach(invite => {
+                    addInviteRow(team, invite)
+                })
+            })
+        }
+    })
+}
+
+function revokeInvite(team, invite){
+    postTeamForm("./public_api/teams/invites/revoke", {team_id: team.id, id: invite.id}).then(response => {
+        response.text().then(text => {
+            showInviteAlert(response.status === 200 ? "success" : "danger", text)
+        })
+        refreshInvites(team)
+    })
+}
+
+
+
+function addTokenRow(token){
+    let row = document.createElement("tr")
+
+    let cells = [
+        token.name,
+        token.scopes.join(", "),
+        timeSince(new Date(token.created)),
+        token.last_used ? timeSince(new Date(token.last_used)) : "Never",
+        token.expires ? new Date(token.expires).toLocaleDateString() : "Never",
+    ]
+
+    cells.forEach(text => {
+        let cell = document.createElement("td")
+        cell.innerText = text
+        row.appendChild(cell)
+    })
+
+    let revokeButton = document.createElement("button")
+    revokeButton.innerText = "Revoke"
+    revokeButton.classList.add("btn", "btn-danger", "btn-sm")
+    revokeButton.onclick = e => {
+        revokeToken(token)
+    }
+
+    let rotateButton = document.createElement("button")
+    rotateButton.innerText = "Rotate"
+    rotateButton.classList.add("btn", "btn-light", "btn-sm", "me-1")
+    rotateButton.onclick = e => {
+        rotateToken(token)
+    }
+
+    let cell = document.createElement("td")
+    cell.appendChild(rotateButton)
+    cell.appendChild(revokeButton)
+    row.appendChild(cell)
+
+    tokens_table.appendChild(row)
+}
+
+function addTokenEventRow(event){
+    let row = document.createElement("tr")
+
+    let cells = [
+        event.token_name,
+        event.event,
+        event.ip ? event.ip : "",
+        new Date(event.created).toLocaleString(),
+    ]
+
+    cells.forEach(text => {
+        let cell = document.createElement("td")
+        cell.innerText = text
+        row.appendChild(cell)
+    })
+
+    token_events_table.appendChild(row)
+}
+
+function refreshTokens(){
+    fetch("./public_api/tokens").then(response => {
+        if (response.status === 200){
+            response.json().then(tokens => {
+                tokens_table.innerHTML = ""
+                tokens.forEach(token => {
+                    addTokenRow(token)
+                })
+                token_count.innerText = tokens.length
+            })
+        }
+    })
+    fetch("./public_api/tokens/events").then(response => {
+        if (response.status === 200){
+            response.json().then(events => {
+                token_events_table.innerHTML = ""
+                events.forEach(event => {
+                    addTokenEventRow(event)
+                })
+            })
+        }
+    })
+}
+refreshTokens()
+
+function createToken(){
+    let form = document.getElementById("create_token_form")
+    if (!form.reportValidity()) return
+
+    let scopes = Array.from(document.querySelectorAll("input[name=token_scope]:checked")).map(i => i.value)
+    document.getElementById("token_publish_mods").value.split(",").forEach(name => {
+        name = name.trim()
+        if (name != "") scopes.push("publish:" + name)
+    })
+
     let f = new FormData()
-    f.set("team_id", team.id)
+    f.set("name", document.getElementById("token_name").value)
+    f.set("scopes", scopes.join(","))
+    let expires_in = document.getElementById("token_expires_in").value
+    if (expires_in != "") f.set("expires_in", expires_in)
     let params = new URLSearchParams(f).toString()
-    fetch("./public_api/teams/invite?" + params, 
+
+    fetch("./public_api/tokens/create",
         {
-            method: "get",
+            method: "post",
             headers: {
+                "Content-Type":"application/x-www-form-urlencoded",
                 "accept-charset":"utf-8"
             },
+            body: params
         }
     ).then(response => {
         if (response.status === 200){
             response.text().then(text => {
-                team_invite.innerText = text
-                team_invite.href = text
+                document.getElementById("created_token").innerText = text
+                document.getElementById("created_token_display").hidden = false
             })
+            refreshTokens()
         }
-        if (response.status === 400){
+        else {
             response.text().then(text => {
-                
+                create_token_alerts.innerHTML += `<div class="alert alert-danger alert-dismissible fade show" role="alert">
+                ${safetext(text)}
+                <button type="button" class="btn-close" data-bs-dismiss="alert" aria-label="Close"></button>
+                </div>`
             })
         }
-    }).catch(error => {
-        team_invite.innerText = error
-        team_invite.href = ""
     })
 }
 
+function showTokenAlert(kind, text){
+    tokens_alerts.innerHTML += `<div class="alert alert-${kind} alert-dismissible fade show" role="alert">
+    ${safetext(text)}
+    <button type="button" class="btn-close" data-bs-dismiss="alert" aria-label="Close"></button>
+    </div>`
+}
+
+function rotateToken(token){
+    let grace_period = prompt(`Rotating "${token.name}". For how many hours should the old token keep working? (0 - 168)`, "0")
+    if (grace_period == null) return
+
+    let f = new FormData()
+    f.set("id", token.id)
+    f.set("grace_period", grace_period)
+    let params = new URLSearchParams(f).toString()
+
+    fetch("./public_api/tokens/rotate",
+        {
+            method: "post",
+            headers: {
+                "Content-Type":"application/x-www-form-urlencoded",
+                "accept-charset":"utf-8"
+            },
+            body: params
+        }
+    ).then(response => {
+        response.text().then(text => {
+            if (response.status === 200){
+                showTokenAlert("success", `The new token for "${token.name}" will only be shown once: ${text}`)
+            }
+            else {
+                showTokenAlert("danger", text)
+            }
+        })
+        refreshTokens()
+    })
+}
+
+function revokeToken(token){
+    if (!confirm(`Revoke the token "${token.name}"? Anything using it will stop working.`)) return
+
+    let f = new FormData()
+    f.set("id", token.id)
+    let params = new URLSearchParams(f).toString()
+
+    fetch("./public_api/tokens/revoke",
+        {
+            method: "post",
+            headers: {
+                "Content-Type":"application/x-www-form-urlencoded",
+                "accept-charset":"utf-8"
+            },
+            body: params
+        }
+    ).then(response => {
+        response.text().then(text => {
+            showTokenAlert(response.status === 200 ? "success" : "danger", text)
+        })
+        refreshTokens()
+    })
+}
+
+function addAccountRow(provider, account){
+    let row = document.createElement("li")
+    row.classList.add("list-group-item", "d-flex", "justify-content-between", "align-items-center")
+    row.innerText = account ? `${provider.display_name}: ${account.username || account.external_id}` : provider.display_name
+
+    let button = document.createElement("button")
+    if (account){
+        button.classList.add("btn", "btn-sm", "btn-outline-danger")
+        button.innerText = "Unlink"
+        button.onclick = e => unlinkAccount(provider)
+    }
+    else {
+        button.classList.add("btn", "btn-sm", "btn-outline-primary")
+        button.innerText = "Link"
+        button.onclick = e => document.location.href = `/login/${provider.name}`
+    }
+    row.appendChild(button)
+    accounts_list.appendChild(row)
+}
+
+function addOwnershipInviteRow(invite){
+    let item = document.createElement("li")
+    item.classList.add("list-group-item", "d-flex", "justify-content-between", "align-items-center")
+
+    let text = document.createElement("span")
+    if (invite.incoming){
+        text.innerText = invite.transfer ? `You have been offered ${invite.mod}` : `You have been invited to co-own ${invite.mod}`
+    }
+    else {
+        text.innerText = `Invited ${invite.invitee_id} to ${invite.transfer ? "take over" : "co-own"} ${invite.mod}`
+    }
+    item.appendChild(text)
+
+    let actions = document.createElement("span")
+    if (invite.incoming){
+        let accept = document.createElement("button")
+        accept.classList.add("btn", "btn-sm", "btn-outline-success", "me-2")
+        accept.innerText = "Accept"
+        accept.onclick = e => answerOwnershipInvite(invite, "accept")
+        actions.appendChild(accept)
+    }
+    let decline = document.createElement("button")
+    decline.classList.add("btn", "btn-sm", "btn-outline-danger")
+    decline.innerText = invite.incoming ? "Decline" : "Cancel"
+    decline.onclick = e => answerOwnershipInvite(invite, "decline")
+    actions.appendChild(decline)
+    item.appendChild(actions)
+
+    document.getElementById("ownership_invites_list").appendChild(item)
+}
+
+function refreshOwnershipInvites(){
+    fetch("./public_api/owners/invites").then(response => {
+        if (response.status === 200){
+            response.json().then(invites => {
+                document.getElementById("ownership_invites_list").innerHTML = ""
+                invites.forEach(invite => {
+                    addOwnershipInviteRow(invite)
+                })
+            })
+        }
+    })
+}
+refreshOwnershipInvites()
+
+function answerOwnershipInvite(invite, answer){
+    postTeamForm(`./public_api/owners/${answer}`, {id: invite.id}).then(response => {
+        response.text().then(text => {
+            alert(text)
+        })
+        if (response.status === 200) reloadMe()
+    })
+}
+
+function refreshAccounts(){
+    Promise.all([fetch("/providers"), fetch("./public_api/accounts")]).then(([providers, accounts]) => {
+        if (providers.status !== 200 || accounts.status !== 200) return
 
+        Promise.all([providers.json(), accounts.json()]).then(([providers, accounts]) => {
+            accounts_list.innerHTML = ""
+            providers.forEach(provider => {
+                addAccountRow(provider, accounts.find(account => account.provider == provider.name))
+            })
+        })
+    })
+}
+refreshAccounts()
+
+function unlinkAccount(provider){
+    if (!confirm(`Unlink your ${provider.display_name} account? You won't be able to log in with it anymore.`)) return
+
+    let f = new FormData()
+    f.set("provider", provider.name)
+    let params = new URLSearchParams(f).toString()
+
+    fetch("./public_api/accounts/unlink",
+        {
+            method: "post",
+            headers: {
+                "Content-Type":"application/x-www-form-urlencoded",
+                "accept-charset":"utf-8"
+            },
+            body: params
+        }
+    ).then(response => {
+        response.text().then(text => {
+            accounts_alerts.innerHTML = `<div class="alert alert-${response.status === 200 ? "success" : "danger"} m-2" role="alert">${text}</div>`
+        })
+        refreshAccounts()
+    })
+}
+
+function addSessionRow(session){
+    let row = document.createElement("tr")
+
+    let cells = [
+        session.user_agent ? session.user_agent : "Unknown",
+        session.ip ? session.ip : "",
+        session.provider ? session.provider : "",
+        new Date(session.created).toLocaleString(),
+        new Date(session.last_seen).toLocaleString(),
+    ]
+
+    cells.forEach(text => {
+        let cell = document.createElement("td")
+        cell.innerText = text
+        row.appendChild(cell)
+    })
+
+    let actions = document.createElement("td")
+    if (session.current){
+        actions.innerHTML = '<span class="badge bg-success">This session</span>'
+    }
+    else {
+        let revoke = document.createElement("button")
+        revoke.classList.add("btn", "btn-sm", "btn-outline-danger")
+        revoke.innerText = "Log out"
+        revoke.onclick = e => revokeSession(session)
+        actions.appendChild(revoke)
+    }
+    row.appendChild(actions)
+
+    sessions_table.appendChild(row)
+}
+
+function refreshSessions(){
+    fetch("./public_api/sessions").then(response => {
+        if (response.status === 200){
+            response.json().then(sessions => {
+                sessions_table.innerHTML = ""
+                sessions.forEach(session => {
+                    addSessionRow(session)
+                })
+                session_count.innerText = sessions.length
+            })
+        }
+    })
+}
+refreshSessions()
+
+function showSessionAlert(kind, text){
+    sessions_alerts.innerHTML = `<div class="alert alert-${kind} alert-dismissible fade show" role="alert">
+        ${safetext(text)}
+        <button type="button" class="btn-close" data-bs-dismiss="alert" aria-label="Close"></button>
+    </div>`
+}
+
+function revokeSession(session){
+    let f = new FormData()
+    f.set("id", session.id)
+    let params = new URLSearchParams(f).toString()
+
+    fetch("./public_api/sessions/revoke",
+        {
+            method: "post",
+            headers: {
+                "Content-Type":"application/x-www-form-urlencoded",
+                "accept-charset":"utf-8"
+            },
+            body: params
+        }
+    ).then(response => {
+        response.text().then(text => {
+            showSessionAlert(response.status === 200 ? "success" : "danger", text)
+        })
+        refreshSessions()
+    })
+}
+
+function revokeOtherSessions(){
+    if (!confirm("Log out every other device?")) return
+
+    fetch("./public_api/sessions/revoke_others", {method: "post"}).then(response => {
+        response.text().then(text => {
+            showSessionAlert(response.status === 200 ? "success" : "danger", text)
+        })
+        refreshSessions()
+    })
+}
 
 function refreshMods(){
     mods_display.innerHTML = ""
@@ -223,23 +969,48 @@ function refreshTeams(){
 }
 refreshTeams()
 
+function setPrivacy(){
+    postTeamForm("./public_api/users/privacy", {
+        profile_public: document.getElementById("profile_public_input").checked,
+        show_teams: document.getElementById("show_teams_input").checked,
+    }).then(response => {
+        if (response.status !== 200) response.text().then(alert)
+    })
+}
+
 function reloadMe(){
     initialize(true)
     refreshMods()
     refreshTeams()
+    refreshTokens()
+    refreshSessions()
+    refreshAccounts()
+    refreshOwnershipInvites()
+}
+
+function selectTab(tab){
+    mods_display.hidden = tab != "mods"
+    teams_display.hidden = tab != "teams"
+    tokens_display.hidden = tab != "tokens"
+    sessions_display.hidden = tab != "sessions"
+    mods_select.classList.toggle("active", tab == "mods")
+    teams_select.classList.toggle("active", tab == "teams")
+    tokens_select.classList.toggle("active", tab == "tokens")
+    sessions_select.classList.toggle("active", tab == "sessions")
+    create_team_button.hidden = tab != "teams"
+    create_token_button.hidden = tab != "tokens"
+    revoke_other_sessions_button.hidden = tab != "sessions"
 }
 
 mods_select.onclick = e => {
-    teams_display.hidden = true
-    mods_display.hidden = false
-    teams_select.classList.remove("active")
-    mods_select.classList.add("active")
-    create_team_button.hidden = true
+    selectTab("mods")
 }
 teams_select.onclick = e => {
-    mods_display.hidden = true
-    teams_display.hidden = false
-    mods_select.classList.remove("active")
-    teams_select.classList.add("active")
-    create_team_button.hidden = false
+    selectTab("teams")
+}
+tokens_select.onclick = e => {
+    selectTab("tokens")
+}
+sessions_select.onclick = e => {
+    selectTab("sessions")
 }
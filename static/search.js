@@ -221,6 +221,9 @@ function addModCard(result) {
 
     labels = document.createElement("div");
     labels.innerHTML = verificationProperties.fromVerificationLevel(result["verification"]).badge;
+    if (result.deprecated !== undefined){
+        labels.innerHTML += ` <span class="badge bg-warning text-dark">Deprecated</span>`
+    }
 
     //result.categories.forEach((category) => {
     //  span = document.createElement("span");
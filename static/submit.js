@@ -64,9 +64,7 @@ function uploadMod(){
             method: "POST",
             mode: "same-origin",
             credentials: "include",
-            headers: {
-                "Authorization": getToken()
-            },
+            headers: authHeaders(),
             body: f
             
         }
@@ -97,6 +95,14 @@ function uploadMod(){
                         <button type="button" class="btn-close" data-bs-dismiss="alert" aria-label="Close"></button>
                     </div>`
                 }
+                if (response.status === 200){
+                    text.split("\n").slice(1).forEach(warning => {
+                        alerts.innerHTML += `<div class="alert alert-warning alert-dismissible fade show" role="alert">
+                            ${safetext(warning)}
+                            <button type="button" class="btn-close" data-bs-dismiss="alert" aria-label="Close"></button>
+                        </div>`
+                    })
+                }
                 document.body.scrollTop = 0;
                 document.documentElement.scrollTop = 0
             });
@@ -36,8 +36,8 @@ const verificationProperties = {
         download_colour: "btn-danger",
         alert: `
         <div class="alert alert-danger" role="alert" id="alert_unsafe">
-            <h4 class="alert-heading mb-0 text-center">Mod <u>failed</u> verification</h4>
-            <p class="my-2">This mod has been yanked by the owner. A download is still available but not recommended!</p>
+            <h4 class="alert-heading mb-0 text-center">Mod <u>yanked</u></h4>
+            <p class="my-2">This mod version has been yanked by the owner. A download is still available but not recommended!</p>
             <hr class="m-1">
             <p class="m-0 small"><em>You can find more information about mod verification <a href="#">here</a>.</em></p>
         </div>`,
@@ -123,50 +123,28 @@ const TeamRoles = {
     MOD      : 0b00000100,
 }
 
-var data, user_data, oauth2_url, logged_in
-
-
-function tryGetToken(force=false){
-    if (((!localStorage.token || localStorage.token == "null") && localStorage.logged_in == "true") || force){
-        fetch("/token")
-            .then(function (response) {
-                if (response.status !== 200){
-                    //console.log(response.status)
-                    //localStorage.logged_in = false
-                    return
-                }
-            
-                response.text().then(function (text) {
-                    //console.log(json_data)
-                    localStorage.token = text
-                })
-            }
-        )
-    }
-}
+var data, user_data, logged_in
 
 let coreMods = []
 
 function initialize(forceReload=false){
     //localStorage.me
-    //localStorage.oauth2_url
     //localStorage.logged_in
     //localStorage.as_team
-    //localStorage.token
     //localStorage.team_id
     //localStorage.team_token
     if (localStorage.as_team == undefined){
         localStorage.as_team = false
     }
     
-    if (localStorage.coreMods == undefined){
-        fetch("/static/core_mods.json")
+    if (sessionStorage.coreMods == undefined){
+        fetch("/public_api/core_mods")
             .then(function (response) {
                 if (response.status == 200){
                     response.json().then(function (json_data){
                         console.log("Fetched core mods.")
                         coreMods = json_data
-                        localStorage.coreMods = JSON.stringify(json_data)
+                        sessionStorage.coreMods = JSON.stringify(json_data)
                         processCoreMods()
                     })
                 }
@@ -175,7 +153,7 @@ function initialize(forceReload=false){
     }
     else {
         try {
-            coreMods = JSON.parse(localStorage.coreMods)
+            coreMods = JSON.parse(sessionStorage.coreMods)
         }
         catch {}
         processCoreMods()
@@ -185,47 +163,28 @@ function initialize(forceReload=false){
         setup(JSON.parse(localStorage.me))
     }
     else { // if not cached, fetch user data
-        fetch("/token").then(
-            function (res){
-                fetch("/public_api/me")
-                    .then(function (response) {
-                        if (response.status !== 200){
-                            console.log(response.status)
-                            localStorage.logged_in = false
-                            return
-                        }
-                    
-                        response.json().then(function (json_data) {
-                            //console.log(json_data)
-                            localStorage.logged_in = true
-                            localStorage.me = JSON.stringify(json_data)
-                            tryGetToken()
-                            setup(json_data)
-                        })
-                    }
-                )
+        fetch("/public_api/me")
+            .then(function (response) {
+                if (response.status !== 200){
+                    console.log(response.status)
+                    localStorage.logged_in = false
+                    return
+                }
+            
+                response.json().then(function (json_data) {
+                    //console.log(json_data)
+                    localStorage.logged_in = true
+                    localStorage.me = JSON.stringify(json_data)
+                    setup(json_data)
+                })
             }
         )
-        
     }
-    tryGetToken()
+    // Tokens used to be fetched on every page, the requests are authenticated by the session now.
+    localStorage.removeItem("token")
     
     if (!localStorage.logged_in || localStorage.logged_in == "false"){
-        localStorage.token = "null"
-        if (!localStorage.oauth2_url){
-            fetch("/oauth2_url")
-                .then(function (response) {
-                    response.json().then(function (res) {
-                        console.log(res)
-                        localStorage.oauth2_url = res.url
-                        document.getElementById("login_button").setAttribute("onclick", `document.location.href = '${localStorage.oauth2_url}'`);
-                    })
-                }
-            )
-        }
-        else {
-            document.getElementById("login_button").setAttribute("onclick", `document.location.href = '${localStorage.oauth2_url}'`);
-        }
+        document.getElementById("login_button").setAttribute("onclick", `document.location.href = '/login'`);
     }
 }
 
@@ -239,8 +198,8 @@ function setup(json_data){
     document.getElementById("user_button").removeAttribute("hidden")
     document.getElementById("user_button").innerHTML = `
         <span class="">
-            <img class="me-2" src="https://cdn.discordapp.com/avatars/${json_data.user_id_string}/${json_data.discord.avatar}.png?size=256" style='width: 32px; height: 32px;'>
-            ${json_data.discord.username}
+            <img class="me-2" src="${json_data.profile.avatar_url || '/static/PolyTech.svg'}" style='width: 32px; height: 32px;'>
+            ${json_data.profile.username}
         </span>`
     
     mod_options = document.getElementById("mod_options")
@@ -249,7 +208,7 @@ function setup(json_data){
     }
     let authors = document.getElementById("authors")
     if (authors) {
-        authors.value = json_data.discord.username
+        authors.value = json_data.profile.username
         $('input[type="tags"]').forEach(tagsInput)
     }
     let team_switcher = document.getElementById("team_switcher")
@@ -277,7 +236,13 @@ function setup(json_data){
 }
 
 function getToken(){
-    return localStorage.as_team == "true" ? localStorage.team_token : localStorage.token
+    return localStorage.as_team == "true" ? localStorage.team_token : ""
+}
+
+// Requests made as the user are authenticated by the session cookie, only teams need a token.
+function authHeaders(){
+    let token = getToken()
+    return token && token != "null" ? {"Authorization": token} : {}
 }
 
 function teamSwitch(){
@@ -294,22 +259,29 @@ function teamSwitch(){
         let f = new FormData()
         f.set("id", team_id)
         let params = new URLSearchParams(f).toString()
-        fetch("./public_api/teams/token?" + params, 
+        fetch("./public_api/teams/token", 
             {
-                method: "get",
+                method: "post",
                 headers: {
+                    "Content-Type":"application/x-www-form-urlencoded",
                     "accept-charset":"utf-8"
                 },
+                body: params
             }
         ).then(response => {
-            if (response.status === 200){
-                response.text().then(text => {
+            response.text().then(text => {
+                if (response.status === 200){
                     localStorage.as_team = true
                     localStorage.team_id = team_id
                     localStorage.team_token = text
                     //location.reload()
-                })
-            }
+                }
+                else {
+                    alert(response.status === 401 ? `You can't act as ${team_name}.` : text)
+                    team_switcher.selectedIndex = 0
+                    teamSwitch()
+                }
+            })
         })
     }
 }
@@ -324,7 +296,7 @@ function processCoreMods(){
         list_item.appendChild(link)
 
         link.classList.add("dropdown-item")
-        link.href = entry.path
+        link.href = `/mod?name=${encodeURIComponent(entry.name)}&verification=Core`
         link.text = entry.name
 
         target.appendChild(list_item)